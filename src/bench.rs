@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::ext::FillableFromEnvironment;
+use crate::{model, PostWomanCollection};
+
+/// how hard to hit each route
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+	/// stop after this many requests
+	pub requests: Option<usize>,
+	/// stop after this much time
+	pub duration: Option<Duration>,
+	/// requests in flight at the same time
+	pub concurrency: usize,
+	/// max requests per second, across all workers
+	pub rate: Option<f64>,
+}
+
+/// outcome of a single benchmark request
+struct Sample {
+	latency: Duration,
+	status: Option<u16>,
+	error: Option<String>,
+}
+
+/// stats collected benchmarking one route
+pub struct BenchResult {
+	pub namespace: String,
+	pub name: String,
+	pub requests: usize,
+	pub errors: usize,
+	/// wall time of the whole benchmark
+	pub elapsed: Duration,
+	/// how many responses got each status, failed requests without a response are not counted
+	pub statuses: BTreeMap<u16, usize>,
+	/// latency of every request, sorted
+	pub latencies: Vec<Duration>,
+	/// first error encountered, to give an idea of what went wrong
+	pub first_error: Option<String>,
+}
+
+impl BenchResult {
+	/// requests completed per second
+	pub fn throughput(&self) -> f64 {
+		self.requests as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+	}
+
+	/// fraction of requests which failed, from 0 to 1
+	pub fn error_rate(&self) -> f64 {
+		if self.requests == 0 { return 0.0 };
+		self.errors as f64 / self.requests as f64
+	}
+
+	/// latency under which given fraction of requests completed, nearest rank
+	pub fn percentile(&self, p: f64) -> Duration {
+		if self.latencies.is_empty() { return Duration::ZERO };
+		let rank = (p * self.latencies.len() as f64).ceil() as usize;
+		self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+	}
+
+	/// no request failed, and route could be benchmarked at all
+	pub fn success(&self) -> bool {
+		self.errors == 0 && self.first_error.is_none()
+	}
+
+	pub fn mean(&self) -> Duration {
+		if self.latencies.is_empty() { return Duration::ZERO };
+		self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32
+	}
+
+	fn new(namespace: String, name: String, elapsed: Duration, samples: Vec<Sample>) -> Self {
+		let mut statuses = BTreeMap::new();
+		let mut latencies = Vec::with_capacity(samples.len());
+		let mut errors = 0;
+		let mut first_error = None;
+		for sample in &samples {
+			latencies.push(sample.latency);
+			if let Some(status) = sample.status {
+				*statuses.entry(status).or_default() += 1;
+			}
+			if let Some(ref error) = sample.error {
+				errors += 1;
+				first_error.get_or_insert_with(|| error.clone());
+			}
+		}
+		latencies.sort();
+		BenchResult { namespace, name, requests: samples.len(), errors, elapsed, statuses, latencies, first_error }
+	}
+}
+
+/// benchmark all routes matching pattern in a collection, one after the other
+pub async fn bench_collection(
+	namespace: String,
+	collection: PostWomanCollection,
+	pattern: &regex::Regex,
+	opts: &BenchOptions,
+) -> Vec<BenchResult> {
+	let env = collection.env;
+	let mut client = collection.client.fill(&env);
+	// cookies can be shared within the benchmark, but never saved
+	if client.keeps_cookies() {
+		client.jar = Some(Default::default());
+	}
+	let client = Arc::new(client);
+	let http = client.client().map_err(|e| e.to_string());
+	let tokens = Arc::new(client.oauth2.clone().map(|x| model::TokenStore::new(x, &namespace)));
+
+	let mut results = Vec::new();
+	for (name, endpoint) in collection.route {
+		let full_name = crate::ext::full_name(&namespace, &name);
+		if pattern.find(&full_name).is_none() { continue };
+
+		let endpoint = endpoint.fill(&env);
+		let (client, http) = match client.for_route(&endpoint) {
+			Some(route_client) => {
+				let http = route_client.client().map_err(|e| e.to_string());
+				(Arc::new(route_client), http)
+			},
+			None => (client.clone(), http.clone()),
+		};
+		let http = match http {
+			Ok(x) => x,
+			Err(e) => {
+				eprintln!("<!>[{}] {full_name} \tinvalid client: {e}", chrono::Local::now().format(crate::fmt::TIMESTAMP_FMT));
+				let mut res = BenchResult::new(namespace.clone(), name, Duration::ZERO, Vec::new());
+				res.first_error = Some(e);
+				results.push(res);
+				continue;
+			},
+		};
+
+		eprintln!(
+			" : [{}] {full_name} \tbenchmarking with {} concurrent requests...",
+			chrono::Local::now().format(crate::fmt::TIMESTAMP_FMT), opts.concurrency
+		);
+		let started = Instant::now();
+		let samples = bench_route(endpoint, client, http, tokens.clone(), opts).await;
+		results.push(BenchResult::new(namespace.clone(), name, started.elapsed(), samples));
+	}
+	results
+}
+
+async fn bench_route(
+	endpoint: model::EndpointConfig,
+	client: Arc<model::ClientConfig>,
+	http: reqwest::Client,
+	tokens: Arc<Option<model::TokenStore>>,
+	opts: &BenchOptions,
+) -> Vec<Sample> {
+	let endpoint = Arc::new(endpoint);
+	let samples = Arc::new(Mutex::new(Vec::new()));
+	let issued = Arc::new(AtomicUsize::new(0));
+	let deadline = opts.duration.map(|x| Instant::now() + x);
+	let limiter = opts.rate.and_then(|rate| model::rate_interval(rate).ok()).map(|period| {
+		let mut interval = tokio::time::interval(period);
+		// if we fall behind, don't burst to catch up, that's not the rate we were asked for
+		interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+		Arc::new(tokio::sync::Mutex::new(interval))
+	});
+
+	let mut workers = tokio::task::JoinSet::new();
+	for _ in 0..opts.concurrency.max(1) {
+		let endpoint = endpoint.clone();
+		let client = client.clone();
+		let http = http.clone();
+		let tokens = tokens.clone();
+		let samples = samples.clone();
+		let issued = issued.clone();
+		let limiter = limiter.clone();
+		let requests = opts.requests;
+
+		workers.spawn(async move {
+			loop {
+				if requests.is_some_and(|n| issued.fetch_add(1, Ordering::Relaxed) >= n) { break };
+				if deadline.is_some_and(|x| Instant::now() >= x) { break };
+				if let Some(ref limiter) = limiter {
+					limiter.lock().await.tick().await;
+					// waiting for our turn may well have taken us past the deadline
+					if deadline.is_some_and(|x| Instant::now() >= x) { break };
+				}
+
+				let mut route = (*endpoint).clone();
+				let before = Instant::now();
+				let sent = route.send_authenticated(&client, &http, tokens.as_ref().as_ref()).await;
+				let latency = before.elapsed();
+				// same as runs, credentials must never show up in outputs, oauth2 token included
+				let mut secrets = route.auth.as_ref().map(|x| x.secrets()).unwrap_or_default();
+				secrets.extend(client.secrets());
+				secrets.extend(route.tls.iter().flat_map(|x| x.secrets()));
+				let (status, error) = match sent {
+					Err(e) => (None, Some(e)),
+					Ok(x) => (Some(x.response.status.as_u16()), route.check(&x).err()),
+				};
+				let error = error.map(|e| model::redact_error(e, &secrets).to_string());
+				samples.lock().expect("bench samples poisoned").push(Sample { latency, status, error });
+			}
+		});
+	}
+	while workers.join_next().await.is_some() {}
+
+	let samples = std::mem::take(&mut *samples.lock().expect("bench samples poisoned"));
+	samples
+}
@@ -4,6 +4,9 @@ pub enum PostWomanError {
 	#[error("network error: {0:?}")]
 	Request(#[from] reqwest::Error),
 
+	#[error("invalid client configuration: {0}")]
+	InvalidClient(String),
+
 	#[error("invalid method: {0:?}")]
 	InvalidMethod(#[from] http::method::InvalidMethod),
 
@@ -25,6 +28,15 @@ pub enum PostWomanError {
 	#[error("could not represent collection: {0:?}")] // should never happen
 	ErrorSerializingInternallyCollection(#[from] toml_edit::ser::Error),
 
+	#[error("authentication failed: {0}")]
+	AuthError(String),
+
+	#[error("tls error: {0}")]
+	TlsError(String),
+
+	#[error("invalid body: {0}")]
+	InvalidBody(String),
+
 	#[error("invalid json payload: {0:?}")]
 	InvalidJson(#[from] serde_json::Error),
 
@@ -32,16 +44,41 @@ pub enum PostWomanError {
 	InvalidRegex(#[from] regex::Error),
 
 	#[error("request didn't match expected status code: {0:?}")]
-	UnexpectedStatusCode(reqwest::Response),
+	UnexpectedStatusCode(Box<crate::model::BufferedResponse>),
+
+	#[error("server negotiated {0}, expected {1}")]
+	UnexpectedHttpVersion(String, String),
 
 	#[error("request didn't match expected result: got '{0}' expected '{1}'")]
 	UnexpectedResult(String, String),
 
+	#[error("failed assertions: {}", .0.join(", "))]
+	FailedAssertions(Vec<String>),
+
 	#[error("invalid Json Query: {0}")]
 	JQError(String),
 
 	#[error("regex failed matching in content: {0}")]
 	NoMatch(String),
+
+	#[error("invalid env profile: {0}")]
+	InvalidProfile(String),
+
+	#[error("invalid rate {0}, expected requests per second above 0 and up to a billion")]
+	InvalidRate(f64),
+
+	#[error("invalid route dependencies: {0}")]
+	InvalidDependency(String),
+
+	#[error("could not import: {0}")]
+	ImportError(String),
+
+	/// any other error, with credentials already scrubbed from its message
+	#[error("{0}")]
+	Redacted(String),
+
+	#[error("route task crashed: {0}")]
+	Panic(String),
 }
 
 #[derive(Debug, thiserror::Error)]
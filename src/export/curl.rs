@@ -0,0 +1,160 @@
+use crate::ext::{urlencode, StringOr};
+use crate::model::{ApiKeyLocation, AuthConfig, BodyConfig, ClientConfig, EndpointConfig, HttpVersion, TypedBody};
+use crate::{PostWomanError, APP_USER_AGENT};
+
+/// build a curl command equivalent to given endpoint, already filled from environment
+pub fn command(endpoint: &EndpointConfig, client: &ClientConfig) -> Result<String, PostWomanError> {
+	// one option per line, like browser devtools do
+	let auth = endpoint.auth.as_ref().or(client.auth.as_ref().filter(|_| client.oauth2.is_none()));
+
+	let mut url = endpoint.url(client.base.as_deref());
+	if let Some(AuthConfig::ApiKey { key, value, location: Some(ApiKeyLocation::Query) }) = auth {
+		let separator = if url.contains('?') { '&' } else { '?' };
+		url = format!("{url}{separator}{}={}", urlencode(key), urlencode(value));
+	}
+
+	let mut lines = vec![format!("curl {}", quote(&url))];
+
+	let method = endpoint.method()?;
+	if method != reqwest::Method::GET {
+		lines.push(format!("-X {}", quote(method.as_str())));
+	}
+
+	for header in endpoint.headers.as_deref().unwrap_or(&[]) {
+		lines.push(format!("-H {}", quote(header)));
+	}
+
+	match auth {
+		// tokens are fetched at runtime, leave a shell variable to fill in
+		None if client.oauth2.is_some() => lines.push(r#"-H "Authorization: Bearer $OAUTH2_TOKEN""#.to_string()),
+		None | Some(AuthConfig::None) => {},
+		Some(AuthConfig::Basic { username, password }) =>
+			lines.push(format!("-u {}", quote(&format!("{username}:{}", password.as_deref().unwrap_or_default())))),
+		Some(AuthConfig::Digest { username, password }) =>
+			lines.push(format!("--digest -u {}", quote(&format!("{username}:{password}")))),
+		Some(AuthConfig::Bearer { token }) =>
+			lines.push(format!("-H {}", quote(&format!("Authorization: Bearer {token}")))),
+		Some(AuthConfig::ApiKey { key, value, location }) => if location.unwrap_or_default() == ApiKeyLocation::Header {
+			lines.push(format!("-H {}", quote(&format!("{key}: {value}"))));
+		},
+	}
+
+	lines.push(format!("-A {}", quote(client.user_agent.as_deref().unwrap_or(APP_USER_AGENT))));
+	lines.push(format!("--max-time {}", client.timeout.unwrap_or(30)));
+
+	// curl doesn't follow redirects by default either
+	if let Some(redirects) = client.redirects {
+		lines.push(format!("-L --max-redirs {redirects}"));
+	}
+
+	if let Some(ref proxy) = client.proxy {
+		lines.push(format!("-x {}", quote(proxy)));
+		if let Some(ref username) = client.proxy_username {
+			lines.push(format!("-U {}", quote(&format!("{username}:{}", client.proxy_password.as_deref().unwrap_or_default()))));
+		}
+		if let Some(ref no_proxy) = client.no_proxy {
+			lines.push(format!("--noproxy {}", quote(no_proxy)));
+		}
+	} else if !client.system_proxy.unwrap_or(true) {
+		lines.push("--noproxy '*'".into());
+	}
+
+	let route_client = client.for_route(endpoint);
+	let client = route_client.as_ref().unwrap_or(client);
+
+	if client.accepts_invalid_certs() {
+		lines.push("-k".into());
+	}
+
+	match client.http_version {
+		None => {},
+		Some(HttpVersion::Http1) => lines.push("--http1.1".into()),
+		Some(HttpVersion::Http2) => lines.push("--http2".into()),
+		Some(HttpVersion::H2c) => lines.push("--http2-prior-knowledge".into()),
+	}
+
+	if let Some(tls) = client.tls.clone() {
+		if let Some(ca) = tls.ca {
+			lines.push(format!("--cacert {}", quote(&ca)));
+		}
+		if let Some(cert) = tls.cert {
+			lines.push(format!("--cert {}", quote(&cert)));
+		}
+		if let Some(key) = tls.key {
+			lines.push(format!("--key {}", quote(&key)));
+		}
+		if let Some(pkcs12) = tls.pkcs12 {
+			let cert = match tls.password {
+				Some(password) => format!("{}:{password}", pkcs12.replace(':', "\\:")),
+				None => pkcs12,
+			};
+			lines.push(format!("--cert-type P12 --cert {}", quote(&cert)));
+		}
+		if let Some(min_version) = tls.min_version {
+			lines.push(format!("--tlsv{}", min_version.trim_start_matches("TLS").trim_start_matches('v').trim()));
+		}
+		// curl pins public keys, not whole certificates, so pins can't be translated
+	}
+
+	// curl reads and writes the same cookies.txt format
+	if let Some(ref jar) = client.cookie_jar {
+		lines.push(format!("-b {0} -c {0}", quote(jar)));
+	}
+
+	let has_content_type = endpoint.headers.as_deref().unwrap_or(&[])
+		.iter()
+		.any(|h| h.split_once(':').is_some_and(|(k, _)| k.trim().eq_ignore_ascii_case("content-type")));
+
+	match endpoint.body {
+		Some(StringOr::T(BodyConfig::Typed(TypedBody::Multipart { ref parts }))) => {
+			for part in parts {
+				let mut value = match (&part.value, &part.file) {
+					(_, Some(file)) => format!("{}=@{file}", part.name),
+					(value, None) => format!("{}={}", part.name, value.as_deref().unwrap_or_default()),
+				};
+				if let Some(ref filename) = part.filename {
+					value.push_str(&format!(";filename={filename}"));
+				}
+				if let Some(ref content_type) = part.content_type {
+					value.push_str(&format!(";type={content_type}"));
+				}
+				lines.push(format!("-F {}", quote(&value)));
+			}
+		},
+		Some(StringOr::T(BodyConfig::Typed(TypedBody::File(ref body)))) => {
+			if !has_content_type {
+				if let (Some(content_type), _) = endpoint.payload()? {
+					lines.push(format!("-H {}", quote(&format!("Content-Type: {content_type}"))));
+				}
+			}
+			if body.raw.unwrap_or(false) {
+				lines.push(format!("--data-binary {}", quote(&format!("@{}", body.file))));
+			} else {
+				// variables in file are already replaced, so send its contents
+				let (_, content) = endpoint.payload()?;
+				lines.push(format!("--data-raw {}", quote(&String::from_utf8_lossy(&content))));
+			}
+		},
+		_ => {
+			let body = endpoint.body()?;
+			if !body.is_empty() {
+				// curl would send data as urlencoded, which is only right for form bodies.
+				// without a Content-Type of ours, an empty header makes curl send none too
+				if !has_content_type {
+					let header = match endpoint.payload()? {
+						(Some(content_type), _) => format!("Content-Type: {content_type}"),
+						(None, _) => "Content-Type:".to_string(),
+					};
+					lines.push(format!("-H {}", quote(&header)));
+				}
+				lines.push(format!("--data-raw {}", quote(&body)));
+			}
+		},
+	}
+
+	Ok(lines.join(" \\\n  "))
+}
+
+fn quote(s: &str) -> String {
+	shell_words::quote(s).to_string()
+}
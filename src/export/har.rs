@@ -0,0 +1,102 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde_json::{json, Value};
+
+use crate::model::Exchange;
+
+/// build a HAR 1.2 archive from exchanges recorded during a run, each entry commented with its route
+pub fn archive(entries: &[(String, Exchange)]) -> Value {
+	json!({
+		"log": {
+			"version": "1.2",
+			"creator": {
+				"name": env!("CARGO_PKG_NAME"),
+				"version": env!("CARGO_PKG_VERSION"),
+			},
+			"entries": entries.iter().map(|(name, x)| entry(name, x)).collect::<Vec<_>>(),
+		}
+	})
+}
+
+fn entry(name: &str, exchange: &Exchange) -> Value {
+	let req = &exchange.request;
+	let res = &exchange.response;
+
+	let query: Vec<Value> = reqwest::Url::parse(&req.url)
+		.map(|url| url.query_pairs().map(|(k, v)| json!({ "name": k, "value": v })).collect())
+		.unwrap_or_default();
+
+	let mut request = json!({
+		"method": req.method.as_str(),
+		"url": req.url,
+		"httpVersion": format!("{:?}", res.version),
+		"cookies": [],
+		"headers": headers(&req.headers),
+		"queryString": query,
+		"headersSize": -1,
+		"bodySize": req.body.len(),
+	});
+	if !req.body.is_empty() {
+		let mime = req.headers.get(http::header::CONTENT_TYPE)
+			.and_then(|x| x.to_str().ok())
+			.unwrap_or_default();
+		request["postData"] = json!({ "mimeType": mime, "text": String::from_utf8_lossy(&req.body) });
+	}
+
+	let mime = res.headers.get(http::header::CONTENT_TYPE)
+		.and_then(|x| x.to_str().ok())
+		.unwrap_or_default();
+	let mut content = json!({ "size": res.body.len(), "mimeType": mime });
+	match std::str::from_utf8(&res.body) {
+		Ok(text) => content["text"] = json!(text),
+		Err(_) => {
+			content["text"] = json!(BASE64_STANDARD.encode(&res.body));
+			content["encoding"] = json!("base64");
+		},
+	}
+
+	let location = res.headers.get(http::header::LOCATION)
+		.and_then(|x| x.to_str().ok())
+		.unwrap_or_default();
+
+	let timings = &exchange.timings;
+	let optional = |d: Option<std::time::Duration>| d.map(millis).unwrap_or(-1.0);
+
+	json!({
+		"startedDateTime": exchange.started.to_rfc3339(),
+		"time": millis(exchange.elapsed),
+		"request": request,
+		"response": {
+			"status": res.status.as_u16(),
+			"statusText": res.status.canonical_reason().unwrap_or_default(),
+			"httpVersion": format!("{:?}", res.version),
+			"cookies": [],
+			"headers": headers(&res.headers),
+			"content": content,
+			"redirectURL": location,
+			"headersSize": -1,
+			"bodySize": res.body.len(),
+		},
+		"cache": {},
+		"timings": {
+			"blocked": -1,
+			"dns": optional(timings.dns),
+			// HAR connect includes ssl, which we can't time on its own
+			"connect": optional(timings.connect),
+			"ssl": -1,
+			"send": 0,
+			"wait": millis(timings.wait),
+			"receive": millis(timings.download),
+		},
+		"comment": name,
+	})
+}
+
+fn headers(map: &http::HeaderMap) -> Vec<Value> {
+	map.iter()
+		.map(|(k, v)| json!({ "name": k.as_str(), "value": String::from_utf8_lossy(v.as_bytes()) }))
+		.collect()
+}
+
+fn millis(d: std::time::Duration) -> f64 {
+	d.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,2 @@
+pub mod curl;
+pub mod har;
@@ -34,13 +34,34 @@ pub fn stringify_json(v: &serde_json::Value) -> String {
 	}
 }
 
+/// percent-encode everything except unreserved characters, like curl's --data-urlencode
+pub fn urlencode(s: &str) -> String {
+	let mut out = String::with_capacity(s.len());
+	for b in s.bytes() {
+		match b {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+			_ => out.push_str(&format!("%{b:02X}")),
+		}
+	}
+	out
+}
+
 // keep it as separate fn so we can change it everywhere easily
 pub fn full_name(namespace: &str, name: &str) -> String {
 	format!("{namespace}:{name}")
 }
 
-pub trait FillableFromEnvironment {
-	fn fill(self, env: &toml::Table) -> Self;
+/// variable values by name, ready to replace `${NAME}` placeholders
+pub type Vars = std::collections::HashMap<String, String>;
+
+pub trait FillableFromEnvironment: Sized {
+	/// replace placeholders with given variables
+	fn fill_vars(self, vars: &Vars) -> Self;
+
+	/// replace placeholders with collection env, process env taking precedence
+	fn fill(self, env: &toml::Table) -> Self {
+		self.fill_vars(&Self::default_vars(env))
+	}
 
 	fn default_vars(env: &toml::Table) -> std::collections::HashMap<String, String> {
 		let mut vars: std::collections::HashMap<String, String> = std::collections::HashMap::default();
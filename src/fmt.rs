@@ -12,37 +12,89 @@ pub trait ReportableResult {
 	fn report(self);
 }
 
-// TODO this is not really nice, maybe a struct? Maybe pass them in some other way??
-pub type RunResult = (Result<String, PostWomanError>, String, String, i64);
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+	/// human readable output
+	#[default]
+	Pretty,
+	/// one json object per route
+	Json,
+	/// JUnit XML document, collections are test suites and routes are test cases
+	Junit,
+	/// Test Anything Protocol stream
+	Tap,
+}
+
+pub struct RunResult {
+	pub namespace: String,
+	pub name: String,
+	/// outcome of route execution, None if route was skipped
+	pub result: Option<Result<String, PostWomanError>>,
+	/// in milliseconds
+	pub elapsed: i64,
+	/// how many times request was sent, 0 if never
+	pub attempts: u32,
+	/// negotiated http version, if a response was received
+	pub version: Option<http::Version>,
+	/// time spent in each request phase, if a response was received
+	pub timings: Option<crate::model::Timings>,
+	/// full request and response, only kept when recording the run
+	pub exchange: Option<crate::model::Exchange>,
+}
+
+impl RunResult {
+	pub fn skipped(namespace: String, name: String) -> Self {
+		RunResult { namespace, name, result: None, elapsed: 0, attempts: 0, version: None, timings: None, exchange: None }
+	}
+
+	/// route which couldn't even complete an attempt
+	pub fn failed(namespace: String, name: String, error: PostWomanError) -> Self {
+		RunResult { result: Some(Err(error)), ..Self::skipped(namespace, name) }
+	}
+
+	pub fn success(&self) -> bool {
+		matches!(self.result, Some(Ok(_)))
+	}
 
-impl PrintableResult for RunResult {
+	fn failure(&self) -> Option<String> {
+		match self.result {
+			Some(Err(ref e)) => Some(e.to_string()),
+			_ => None,
+		}
+	}
+}
+
+impl PrintableResult for &RunResult {
 	fn print(self) {
-		let (result, _namespace, _name, _elapsed) = self;
-		match result {
-			Ok(x) => println!("{x}"),
-			Err(e) => eprintln!(" ! {e}"),
+		match self.result {
+			Some(Ok(ref x)) => println!("{x}"),
+			Some(Err(ref e)) => eprintln!(" ! {e}"),
+			None => {},
 		}
 	}
 }
 
-impl ReportableResult for RunResult {
+impl ReportableResult for &RunResult {
 	fn report(self) {
-		let (res, namespace, name, elapsed) = self;
-		let success = res.is_ok();
-		let result = match res {
-			Ok(x) => x,
-			Err(e) => e.to_string(),
+		let result = match self.result {
+			Some(Ok(ref x)) => x.clone(),
+			Some(Err(ref e)) => e.to_string(),
+			None => "skipped".to_string(),
 		};
 
 		println!(
 			"{}",
 			serde_json::to_string(
 				&serde_json::json!({
-					"namespace": namespace,
-					"route": name,
-					"success": success,
+					"namespace": self.namespace,
+					"route": self.name,
+					"success": self.success(),
+					"skipped": self.result.is_none(),
 					"result": result,
-					"elapsed": elapsed,
+					"elapsed": self.elapsed,
+					"attempts": self.attempts,
+					"http_version": self.version.map(|x| format!("{x:?}")),
+					"timings": self.timings,
 				})
 			)
 				.expect("failed serializing literal json")
@@ -50,6 +102,181 @@ impl ReportableResult for RunResult {
 	}
 }
 
+/// collects route results during a run, emitting them in requested format
+pub struct RunReport {
+	format: OutputFormat,
+	started: chrono::DateTime<chrono::Local>,
+	/// only kept for formats which need the whole run before being emitted
+	results: Vec<RunResult>,
+	/// where to save recorded exchanges as HAR archive, if requested
+	har: Option<(std::path::PathBuf, Vec<(String, crate::model::Exchange)>)>,
+	pub passed: usize,
+	pub failed: usize,
+	pub skipped: usize,
+}
+
+impl RunReport {
+	pub fn new(format: OutputFormat, har: Option<std::path::PathBuf>) -> Self {
+		if format == OutputFormat::Tap {
+			println!("TAP version 13");
+		}
+
+		RunReport {
+			format,
+			started: chrono::Local::now(),
+			results: Vec::new(),
+			har: har.map(|path| (path, Vec::new())),
+			passed: 0,
+			failed: 0,
+			skipped: 0,
+		}
+	}
+
+	pub fn push(&mut self, mut res: RunResult) {
+		if let (Some((_, entries)), Some(exchange)) = (&mut self.har, res.exchange.take()) {
+			entries.push((crate::ext::full_name(&res.namespace, &res.name), exchange));
+		}
+
+		match res.result {
+			None => self.skipped += 1,
+			Some(Ok(_)) => self.passed += 1,
+			Some(Err(_)) => self.failed += 1,
+		}
+
+		match self.format {
+			OutputFormat::Pretty => res.print(),
+			OutputFormat::Json => res.report(),
+			OutputFormat::Tap => print_tap(self.passed + self.failed + self.skipped, &res),
+			OutputFormat::Junit => self.results.push(res),
+		}
+	}
+
+	pub fn finish(self) -> std::process::ExitCode {
+		let elapsed = (chrono::Local::now() - self.started).num_milliseconds();
+
+		match self.format {
+			OutputFormat::Pretty | OutputFormat::Json => {},
+			OutputFormat::Tap => println!("1..{}", self.passed + self.failed + self.skipped),
+			OutputFormat::Junit => print_junit(&self.results, elapsed),
+		}
+
+		let mut failed = self.failed > 0;
+
+		if let Some((path, entries)) = self.har {
+			let archive = crate::export::har::archive(&entries);
+			let written = serde_json::to_string_pretty(&archive)
+				.map_err(std::io::Error::from)
+				.and_then(|x| std::fs::write(&path, x));
+			match written {
+				Ok(()) => eprintln!(" + saved {} exchanges into {path:?}", entries.len()),
+				Err(e) => {
+					eprintln!("! could not save HAR archive {path:?}: {e}");
+					failed = true;
+				},
+			}
+		}
+
+		eprintln!(
+			" = [{}] {} passed, {} failed, {} skipped in {elapsed}ms",
+			chrono::Local::now().format(TIMESTAMP_FMT), self.passed, self.failed, self.skipped
+		);
+
+		if failed {
+			std::process::ExitCode::FAILURE
+		} else {
+			std::process::ExitCode::SUCCESS
+		}
+	}
+}
+
+fn print_tap(n: usize, res: &RunResult) {
+	let name = crate::ext::full_name(&res.namespace, &res.name);
+	match res.result {
+		None => println!("ok {n} - {name} # SKIP"),
+		Some(Ok(_)) => println!("ok {n} - {name}"),
+		Some(Err(ref e)) => {
+			println!("not ok {n} - {name}");
+			println!("  ---");
+			println!("  message: {}", serde_json::to_string(&e.to_string()).expect("strings are valid json"));
+			println!("  elapsed: {}", res.elapsed);
+			println!("  ...");
+		},
+	}
+}
+
+fn print_junit(results: &[RunResult], elapsed: i64) {
+	let mut suites: IndexMap<&str, Vec<&RunResult>> = IndexMap::new();
+	for res in results {
+		suites.entry(res.namespace.as_str()).or_default().push(res);
+	}
+
+	let count = |cases: &[&RunResult]| (
+		cases.len(),
+		cases.iter().filter(|x| x.failure().is_some()).count(),
+		cases.iter().filter(|x| x.result.is_none()).count(),
+	);
+	let (tests, failures, skipped) = count(&results.iter().collect::<Vec<_>>());
+
+	println!(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+	println!(
+		r#"<testsuites name="postwoman" tests="{tests}" failures="{failures}" skipped="{skipped}" time="{}">"#,
+		seconds(elapsed)
+	);
+	for (namespace, cases) in suites {
+		let (tests, failures, skipped) = count(&cases);
+		let time = cases.iter().map(|x| x.elapsed).sum();
+		println!(
+			r#"  <testsuite name="{}" tests="{tests}" failures="{failures}" skipped="{skipped}" time="{}">"#,
+			escape_xml(namespace), seconds(time)
+		);
+		for case in cases {
+			print!(
+				r#"    <testcase name="{}" classname="{}" time="{}""#,
+				escape_xml(&case.name), escape_xml(namespace), seconds(case.elapsed)
+			);
+			match case.result {
+				None => println!(">\n      <skipped/>\n    </testcase>"),
+				Some(Ok(ref x)) if x.is_empty() => println!("/>"),
+				Some(Ok(ref x)) => println!(">\n      <system-out>{}</system-out>\n    </testcase>", escape_xml(x)),
+				Some(Err(ref e)) => {
+					let msg = escape_xml(&e.to_string());
+					println!(">\n      <failure message=\"{msg}\">{msg}</failure>\n    </testcase>");
+				},
+			}
+		}
+		println!("  </testsuite>");
+	}
+	println!("</testsuites>");
+}
+
+fn seconds(ms: i64) -> String {
+	format!("{:.3}", ms as f64 / 1000.0)
+}
+
+fn escape_xml(s: &str) -> String {
+	// control characters are not allowed in xml 1.0, even escaped
+	s.replace(|c: char| c.is_control() && !matches!(c, '\t' | '\n' | '\r'), "")
+		.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&apos;")
+}
+
+/// env variables used as credentials by any auth or proxy config in collection
+fn secret_vars(collection: &PostWomanCollection) -> Vec<String> {
+	let secrets: Vec<String> = collection.route.values()
+		.chain(collection.setup.values())
+		.chain(collection.teardown.values())
+		.flat_map(|x| x.auth.iter().flat_map(|x| x.secrets()).chain(x.tls.iter().flat_map(|x| x.secrets())))
+		.chain(collection.client.secrets())
+		.collect();
+	collection.env.keys()
+		.filter(|k| secrets.iter().any(|s| s.contains(&format!("${{{k}}}"))))
+		.cloned()
+		.collect()
+}
+
 // TODO the last tuple element is "compact"... this really needs a better way, maybe a struct!!
 pub type ListResult = (IndexMap<String, PostWomanCollection>, bool);
 
@@ -59,11 +286,26 @@ impl PrintableResult for ListResult {
 		for (namespace, collection) in collections {
 			println!("-> {namespace}");
 
+			let secrets = secret_vars(&collection);
 			for (key, value) in collection.env {
-				println!(" + {key}={}", crate::ext::stringify_toml(&value));
+				let value = if secrets.contains(&key) {
+					crate::model::REDACTED.to_string()
+				} else {
+					crate::ext::stringify_toml(&value)
+				};
+				// process env wins over anything in collections, better to be upfront about it
+				let source = if std::env::var_os(&key).is_some() {
+					" \t(overridden by process env)".to_string()
+				} else {
+					collection.env_profile.get(&key).map(|x| format!(" \t(from profile {x})")).unwrap_or_default()
+				};
+				println!(" + {key}={value}{source}");
 			}
 
-			for (name, endpoint) in collection.route {
+			let routes = collection.setup.into_iter().map(|(name, x)| (format!("setup.{name}"), x))
+				.chain(collection.route)
+				.chain(collection.teardown.into_iter().map(|(name, x)| (format!("teardown.{name}"), x)));
+			for (name, endpoint) in routes {
 				let url = endpoint.url(collection.client.base.as_deref())
 					.split('?')
 					.next()
@@ -82,6 +324,12 @@ impl PrintableResult for ListResult {
 							println!("   |: {header}");
 						}
 					}
+					for dep in endpoint.depends_on.iter().flatten() {
+						println!("   |^ {dep}");
+					}
+					if let Some(auth) = endpoint.auth.as_ref().or(collection.client.auth.as_ref()) {
+						println!("   |@ {auth}");
+					}
 					if let Some(ref _x) = endpoint.body {
 						if let Ok(body) = endpoint.body() {
 							println!("   |> {}", body.replace("\n", "\n   |> "));
@@ -99,7 +347,86 @@ impl PrintableResult for ListResult {
 
 impl ReportableResult for ListResult {
 	fn report(self) {
-		let (collections, _compact) = self;
+		let (mut collections, _compact) = self;
+		for collection in collections.values_mut() {
+			for key in secret_vars(collection) {
+				if let Some(value) = collection.env.get_mut(&key) {
+					*value = toml::Value::String(crate::model::REDACTED.to_string());
+				}
+			}
+			collection.client = collection.client.redacted();
+			for endpoint in collection.route.values_mut().chain(collection.setup.values_mut()).chain(collection.teardown.values_mut()) {
+				endpoint.auth = endpoint.auth.as_ref().map(|x| x.redacted());
+				endpoint.tls = endpoint.tls.as_ref().map(|x| x.redacted());
+			}
+		}
 		println!("{}", serde_json::to_string(&collections).expect("collections are not valid json"));
 	}
 }
+
+fn ms(d: std::time::Duration) -> f64 {
+	d.as_secs_f64() * 1000.0
+}
+
+impl PrintableResult for &crate::bench::BenchResult {
+	fn print(self) {
+		let symbol = if self.success() { " = " } else { "<!>" };
+		println!(
+			"{symbol}{} \t{} requests in {:.2}s, {:.1} req/s",
+			crate::ext::full_name(&self.namespace, &self.name), self.requests, self.elapsed.as_secs_f64(), self.throughput()
+		);
+		println!("   | ok {}, errors {} ({:.1}%)", self.requests - self.errors, self.errors, self.error_rate() * 100.0);
+		if !self.statuses.is_empty() {
+			let statuses = self.statuses.iter()
+				.map(|(status, n)| format!("{status}: {n}"))
+				.collect::<Vec<_>>()
+				.join(", ");
+			println!("   | status {statuses}");
+		}
+		if !self.latencies.is_empty() {
+			println!(
+				"   | latency min {:.1}ms, mean {:.1}ms, p50 {:.1}ms, p90 {:.1}ms, p99 {:.1}ms, max {:.1}ms",
+				ms(self.latencies[0]), ms(self.mean()), ms(self.percentile(0.5)), ms(self.percentile(0.9)),
+				ms(self.percentile(0.99)), ms(self.latencies[self.latencies.len() - 1]),
+			);
+		}
+		if let Some(ref e) = self.first_error {
+			println!("   | first error: {e}");
+		}
+	}
+}
+
+impl ReportableResult for &crate::bench::BenchResult {
+	fn report(self) {
+		let statuses: serde_json::Map<String, serde_json::Value> = self.statuses.iter()
+			.map(|(status, n)| (status.to_string(), (*n).into()))
+			.collect();
+
+		println!(
+			"{}",
+			serde_json::to_string(
+				&serde_json::json!({
+					"namespace": self.namespace,
+					"route": self.name,
+					"success": self.success(),
+					"requests": self.requests,
+					"errors": self.errors,
+					"error_rate": self.error_rate(),
+					"duration": ms(self.elapsed),
+					"throughput": self.throughput(),
+					"statuses": statuses,
+					"latency": {
+						"min": self.latencies.first().copied().map(ms),
+						"mean": ms(self.mean()),
+						"p50": ms(self.percentile(0.5)),
+						"p90": ms(self.percentile(0.9)),
+						"p99": ms(self.percentile(0.99)),
+						"max": self.latencies.last().copied().map(ms),
+					},
+					"first_error": self.first_error,
+				})
+			)
+				.expect("failed serializing literal json")
+		);
+	}
+}
@@ -0,0 +1,278 @@
+use crate::ext::{urlencode, StringOr};
+use crate::model::{AuthConfig, BodyConfig, EndpointConfig, FileBody, TlsConfig, TypedBody};
+use crate::PostWomanError;
+
+// curl flags which only affect its own output or logging, safe to drop
+const IGNORED_FLAGS: &[&str] = &[
+	"-s", "--silent", "-S", "--show-error", "-v", "--verbose", "-i", "--include",
+	"--compressed", "-#", "--progress-bar", "-f", "--fail", "-g", "--globoff",
+];
+
+// curl flags which take a value we don't use
+const IGNORED_OPTIONS: &[&str] = &["-o", "--output", "-w", "--write-out"];
+
+// short flags without a value, which can be clustered like `-sSL`
+const CLUSTER_FLAGS: &str = "sSvifgkLGI";
+
+// short flags taking a value, which may end a cluster like `-ku user:pw` or `-sXPOST`
+const VALUE_FLAGS: &str = "XHdubAeowm";
+
+/// build an endpoint from a curl command line, already split in words
+pub fn endpoint(args: &[String], base: Option<&str>) -> Result<EndpointConfig, PostWomanError> {
+	let args: Vec<String> = args.iter().flat_map(|a| expand_cluster(a)).collect();
+	let mut args = args.iter().map(|x| x.as_str()).peekable();
+	if args.peek() == Some(&"curl") {
+		args.next();
+	}
+
+	let mut url = None;
+	let mut method = None;
+	let mut headers = Vec::new();
+	let mut data: Vec<String> = Vec::new();
+	let mut get = false;
+	let mut json = false;
+	let mut file_body = None;
+	let mut user = None;
+	let mut digest = false;
+	let mut insecure = false;
+
+	while let Some(arg) = args.next() {
+		// support both `-XPOST` and `--request=POST` forms, splitting them like separate words
+		let (flag, inline) = match arg {
+			a if a.starts_with("--") => match a.split_once('=') {
+				Some((f, v)) => (f, Some(v)),
+				None => (a, None),
+			},
+			a if a.starts_with('-') && a.len() > 2 && VALUE_FLAGS.as_bytes().contains(&a.as_bytes()[1]) => (&a[..2], Some(&a[2..])),
+			a => (a, None),
+		};
+		let mut value = || inline
+			.or_else(|| args.next())
+			.map(|x| x.to_string())
+			.ok_or_else(|| PostWomanError::ImportError(format!("missing value for curl option {flag}")));
+
+		match flag {
+			"-X" | "--request" => method = Some(value()?),
+			"-H" | "--header" => headers.push(value()?),
+			"-A" | "--user-agent" => headers.push(format!("User-Agent: {}", value()?)),
+			"-e" | "--referer" => headers.push(format!("Referer: {}", value()?)),
+			"-b" | "--cookie" => headers.push(format!("Cookie: {}", value()?)),
+			"-u" | "--user" => user = Some(value()?),
+			"--digest" => digest = true,
+			"--basic" => {},
+			"-d" | "--data" | "--data-ascii" | "--data-binary" => {
+				let value = value()?;
+				match value.strip_prefix('@') {
+					Some(file) => file_body = Some(FileBody::new(file.to_string(), flag == "--data-binary")),
+					None => data.push(value),
+				}
+			},
+			"--data-raw" => data.push(value()?),
+			"--data-urlencode" => {
+				let value = value()?;
+				data.push(match value.split_once('=') {
+					Some(("", content)) => urlencode(content),
+					Some((name, content)) => format!("{name}={}", urlencode(content)),
+					None => urlencode(&value),
+				});
+			},
+			"--json" => {
+				data.push(value()?);
+				json = true;
+			},
+			"-G" | "--get" => get = true,
+			"-I" | "--head" => method = Some("HEAD".to_string()),
+			"--url" => url = Some(value()?),
+			"-k" | "--insecure" => insecure = true,
+			"-L" | "--location" => eprintln!("! ignoring {flag}, set `redirects` on collection client"),
+			"-m" | "--max-time" => eprintln!("! ignoring {flag} {}, set `timeout` on collection client", value()?),
+			f if IGNORED_FLAGS.contains(&f) => {},
+			f if IGNORED_OPTIONS.contains(&f) => { value()?; },
+			f if f.starts_with('-') => eprintln!("! unsupported curl option {f}, ignoring it"),
+			u => url = Some(u.to_string()),
+		}
+	}
+
+	let Some(url) = url else {
+		return Err(PostWomanError::ImportError("no url in curl command".to_string()));
+	};
+
+	let (path, absolute, mut query) = super::split_url(&url, base);
+
+	let mut body = None;
+	if let Some(file) = file_body {
+		if !data.is_empty() || get {
+			return Err(PostWomanError::ImportError("mixing body files with other data is not supported".to_string()));
+		}
+		if method.is_none() {
+			method = Some("POST".to_string());
+		}
+		body = Some(StringOr::T(BodyConfig::Typed(TypedBody::File(file))));
+	} else if !data.is_empty() {
+		let data = data.join("&");
+		if get {
+			query.get_or_insert_with(Vec::new).extend(data.split('&').map(|x| x.to_string()));
+		} else {
+			if method.is_none() {
+				method = Some("POST".to_string());
+			}
+			// curl sends these by default, unless overridden
+			let content_type = if json { "application/json" } else { "application/x-www-form-urlencoded" };
+			if !has_header(&headers, "content-type") {
+				headers.push(format!("Content-Type: {content_type}"));
+			}
+			if json && !has_header(&headers, "accept") {
+				headers.push("Accept: application/json".to_string());
+			}
+			body = super::json_body(&data);
+		}
+	}
+
+	let auth = user.map(|user| {
+		let (username, password) = match user.split_once(':') {
+			Some((u, p)) => (u.to_string(), Some(p.to_string())),
+			None => (user, None),
+		};
+		if digest {
+			AuthConfig::Digest { username, password: password.unwrap_or_default() }
+		} else {
+			AuthConfig::Basic { username, password }
+		}
+	});
+
+	Ok(EndpointConfig {
+		path,
+		absolute,
+		method: method.filter(|m| m != "GET"),
+		query,
+		headers: if headers.is_empty() { None } else { Some(headers) },
+		body,
+		auth,
+		tls: if insecure { Some(TlsConfig { accept_invalid_certs: Some(true), ..Default::default() }) } else { None },
+		..Default::default()
+	})
+}
+
+/// split clusters of short flags like `-sSL` or `-ku user:pw` into separate words, other args are left alone
+fn expand_cluster(arg: &str) -> Vec<String> {
+	let Some(cluster) = arg.strip_prefix('-') else { return vec![arg.to_string()] };
+	// a value flag first is just an inline value, like `-XPOST`, which is handled later
+	if cluster.len() < 2 || !cluster.starts_with(|c| CLUSTER_FLAGS.contains(c)) {
+		return vec![arg.to_string()];
+	}
+
+	let mut out = Vec::new();
+	for (i, c) in cluster.char_indices() {
+		if CLUSTER_FLAGS.contains(c) {
+			out.push(format!("-{c}"));
+		} else if VALUE_FLAGS.contains(c) {
+			// rest of cluster is its value, if any, otherwise it's the next word
+			out.push(format!("-{c}"));
+			let value = &cluster[i + c.len_utf8()..];
+			if !value.is_empty() {
+				out.push(value.to_string());
+			}
+			return out;
+		} else {
+			return vec![arg.to_string()];
+		}
+	}
+	out
+}
+
+fn has_header(headers: &[String], key: &str) -> bool {
+	headers.iter().any(|h| h.split_once(':').is_some_and(|(k, _)| k.trim().eq_ignore_ascii_case(key)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn words(cmd: &str) -> Vec<String> {
+		shell_words::split(cmd).expect("valid shell words")
+	}
+
+	#[test]
+	fn expands_flag_clusters() {
+		assert_eq!(expand_cluster("-sSL"), ["-s", "-S", "-L"]);
+		assert_eq!(expand_cluster("-ku"), ["-k", "-u"]);
+		assert_eq!(expand_cluster("-kuuser:pw"), ["-k", "-u", "user:pw"]);
+		assert_eq!(expand_cluster("-sXPOST"), ["-s", "-X", "POST"]);
+		// inline values and unknown flags are left alone
+		assert_eq!(expand_cluster("-XPOST"), ["-XPOST"]);
+		assert_eq!(expand_cluster("-d@file.json"), ["-d@file.json"]);
+		assert_eq!(expand_cluster("-sZ"), ["-sZ"]);
+		assert_eq!(expand_cluster("--silent"), ["--silent"]);
+		assert_eq!(expand_cluster("https://example.com"), ["https://example.com"]);
+	}
+
+	#[test]
+	fn keeps_credentials_from_clustered_user() {
+		let endpoint = endpoint(&words("curl -ku user:pw https://example.com/x"), None).expect("valid command");
+		match endpoint.auth {
+			Some(AuthConfig::Basic { username, password }) => {
+				assert_eq!(username, "user");
+				assert_eq!(password.as_deref(), Some("pw"));
+			},
+			other => panic!("expected basic auth, got {other:?}"),
+		}
+	}
+
+	#[test]
+	fn parses_inline_and_separate_values() {
+		let endpoint = endpoint(&words("curl -XPUT --header='X-A: 1' -H 'X-B: 2' --url https://example.com/x?a=1"), None)
+			.expect("valid command");
+		assert_eq!(endpoint.method.as_deref(), Some("PUT"));
+		assert_eq!(endpoint.headers, Some(vec!["X-A: 1".to_string(), "X-B: 2".to_string()]));
+		assert_eq!(endpoint.path, "https://example.com/x");
+		assert_eq!(endpoint.query, Some(vec!["a=1".to_string()]));
+	}
+
+	#[test]
+	fn splits_inline_values_of_any_value_flag() {
+		let endpoint = endpoint(&words("curl -m30 -o/dev/null -wx -ujoe https://example.com"), None).expect("valid command");
+		assert!(matches!(endpoint.auth, Some(AuthConfig::Basic { ref username, password: None }) if username == "joe"));
+		assert_eq!(endpoint.path, "https://example.com");
+	}
+
+	#[test]
+	fn insecure_skips_certificate_checks_on_route() {
+		let insecure = endpoint(&words("curl -sk https://example.com"), None).expect("valid command");
+		assert_eq!(insecure.tls.and_then(|x| x.accept_invalid_certs), Some(true));
+		let secure = endpoint(&words("curl https://example.com"), None).expect("valid command");
+		assert!(secure.tls.is_none());
+	}
+
+	#[test]
+	fn strips_matching_base() {
+		let endpoint = endpoint(&words("curl https://example.com/api/users"), Some("https://example.com/api")).expect("valid command");
+		assert_eq!(endpoint.path, "/users");
+		assert_eq!(endpoint.absolute, None);
+	}
+
+	#[test]
+	fn keeps_json_key_order() {
+		let endpoint = endpoint(&words(r#"curl https://example.com -d '{"name":"x","n":1}'"#), None).expect("valid command");
+		assert_eq!(endpoint.method.as_deref(), Some("POST"));
+		match endpoint.body {
+			Some(StringOr::T(BodyConfig::Json(table))) => {
+				assert_eq!(table.keys().collect::<Vec<_>>(), ["name", "n"]);
+			},
+			other => panic!("expected json body, got {other:?}"),
+		}
+	}
+
+	#[test]
+	fn get_moves_data_into_query() {
+		let endpoint = endpoint(&words("curl -G https://example.com -d a=1 --data-urlencode 'b=x y'"), None).expect("valid command");
+		assert_eq!(endpoint.method, None);
+		assert_eq!(endpoint.query, Some(vec!["a=1".to_string(), "b=x%20y".to_string()]));
+		assert!(endpoint.body.is_none());
+	}
+
+	#[test]
+	fn fails_without_url_or_value() {
+		assert!(endpoint(&words("curl -s"), None).is_err());
+		assert!(endpoint(&words("curl https://example.com -H"), None).is_err());
+	}
+}
@@ -0,0 +1,136 @@
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use crate::ext::{urlencode, StringOr};
+use crate::model::EndpointConfig;
+use crate::PostWomanError;
+
+// headers set by browsers or transport, which reqwest manages on its own
+const SKIPPED_HEADERS: &[&str] = &["host", "content-length", "connection", "accept-encoding", "transfer-encoding"];
+
+// headers carrying live browser session credentials, which must not end up saved in a collection
+const SESSION_HEADERS: &[&str] = &["cookie", "authorization", "proxy-authorization", "x-csrf-token", "x-xsrf-token"];
+
+/// parse a HAR archive, returning entries in order as (method, url, request)
+pub fn parse(raw: &str) -> Result<Vec<(String, String, Value)>, PostWomanError> {
+	let har: Value = serde_json::from_str(raw)?;
+	let Some(entries) = har.pointer("/log/entries").and_then(|x| x.as_array()) else {
+		return Err(PostWomanError::ImportError("not a HAR archive, missing log entries".to_string()));
+	};
+
+	Ok(
+		entries.iter()
+			.filter_map(|e| e.get("request"))
+			.filter_map(|r| Some((
+				r.get("method")?.as_str()?.to_uppercase(),
+				r.get("url")?.as_str()?.to_string(),
+				r.clone(),
+			)))
+			.filter(|(_, url, _)| url.starts_with("http://") || url.starts_with("https://"))
+			.collect()
+	)
+}
+
+/// scheme and host shared by all entries, if any
+pub fn origin(entries: &[(String, String, Value)]) -> Option<String> {
+	let origin_of = |url: &str| url.splitn(4, '/').take(3).collect::<Vec<_>>().join("/");
+	let first = origin_of(&entries.first()?.1);
+	if entries.iter().all(|(_, url, _)| origin_of(url) == first) {
+		Some(first)
+	} else {
+		None
+	}
+}
+
+/// one route for each distinct method and url, repeated requests are only imported once
+pub fn routes(entries: &[(String, String, Value)], base: Option<&str>) -> IndexMap<String, EndpointConfig> {
+	let mut seen = std::collections::HashSet::new();
+	let mut routes = IndexMap::new();
+	let mut dropped = indexmap::IndexSet::new();
+
+	for (method, url, request) in entries {
+		if !seen.insert((method, url)) { continue };
+
+		let (path, absolute, query) = super::split_url(url, base);
+
+		let mut headers: Vec<String> = request.get("headers")
+			.and_then(|x| x.as_array())
+			.into_iter()
+			.flatten()
+			.filter_map(|h| Some((h.get("name")?.as_str()?, h.get("value")?.as_str()?)))
+			// http2 pseudo headers like `:authority` are not real headers
+			.filter(|(k, _)| !k.starts_with(':') && !SKIPPED_HEADERS.contains(&k.to_lowercase().as_str()))
+			.filter(|(k, _)| {
+				let session = SESSION_HEADERS.contains(&k.to_lowercase().as_str());
+				if session { dropped.insert(k.to_string()); }
+				!session
+			})
+			.map(|(k, v)| format!("{k}: {v}"))
+			.collect();
+
+		let post = request.get("postData");
+		let mime = post.and_then(|x| x.get("mimeType")).and_then(|x| x.as_str()).unwrap_or_default();
+		let body = match post.and_then(|x| x.get("text")).and_then(|x| x.as_str()) {
+			Some(text) if mime.contains("json") => super::json_body(text),
+			Some(text) => Some(text.to_string()).filter(|x| !x.is_empty()).map(StringOr::Str),
+			None => {
+				let fields: Vec<String> = post
+					.and_then(|x| x.get("params"))
+					.and_then(|x| x.as_array())
+					.into_iter()
+					.flatten()
+					.filter_map(|p| Some(format!("{}={}", urlencode(p.get("name")?.as_str()?), urlencode(p.get("value")?.as_str()?))))
+					.collect();
+				if fields.is_empty() { None } else { Some(StringOr::Str(fields.join("&"))) }
+			},
+		};
+
+		if body.is_some() && !mime.is_empty() && !headers.iter().any(|h| h.to_lowercase().starts_with("content-type:")) {
+			headers.push(format!("Content-Type: {mime}"));
+		}
+
+		let endpoint = EndpointConfig {
+			path,
+			absolute,
+			method: if method == "GET" { None } else { Some(method.clone()) },
+			query,
+			headers: if headers.is_empty() { None } else { Some(headers) },
+			body,
+			..Default::default()
+		};
+
+		let name = super::route_name(method, &endpoint.path);
+		let name = super::unique_name(&name, |n| routes.contains_key(n));
+		routes.insert(name, endpoint);
+	}
+
+	if !dropped.is_empty() {
+		let dropped = dropped.into_iter().collect::<Vec<_>>().join(", ");
+		eprintln!("! dropped session headers ({dropped}), configure `auth` or `cookies` on collection instead");
+	}
+
+	routes
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn drops_session_headers() {
+		let entries = parse(r#"{ "log": { "entries": [ { "request": {
+			"method": "GET",
+			"url": "https://example.com/me",
+			"headers": [
+				{ "name": "Cookie", "value": "session=live" },
+				{ "name": "Authorization", "value": "Bearer live" },
+				{ "name": "Host", "value": "example.com" },
+				{ "name": "X-Custom", "value": "kept" }
+			]
+		} } ] } }"#).expect("valid archive");
+
+		let routes = routes(&entries, Some("https://example.com"));
+		let endpoint = routes.values().next().expect("one route");
+		assert_eq!(endpoint.headers, Some(vec!["X-Custom: kept".to_string()]));
+	}
+}
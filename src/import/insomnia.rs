@@ -0,0 +1,339 @@
+use serde_json::Value;
+
+use crate::ext::{urlencode, StringOr};
+use crate::model::{ApiKeyLocation, AuthConfig, ClientConfig, EndpointConfig};
+use crate::{PostWomanCollection, PostWomanError};
+
+use super::{convert_template, ImportedFolder};
+
+/// parse an insomnia v4 export into root collection and its folders
+pub fn collection(raw: &str) -> Result<(PostWomanCollection, Vec<ImportedFolder>), PostWomanError> {
+	let export: Value = serde_json::from_str(raw)?;
+
+	if export.get("__export_format").and_then(|x| x.as_u64()) != Some(4) {
+		return Err(PostWomanError::ImportError("only insomnia v4 exports are supported".to_string()));
+	}
+
+	let resources = export.get("resources")
+		.and_then(|x| x.as_array())
+		.cloned()
+		.unwrap_or_default();
+	let kind = |r: &Value| r.get("_type").and_then(|x| x.as_str()).unwrap_or_default().to_string();
+	let id = |r: &Value| r.get("_id").and_then(|x| x.as_str()).unwrap_or_default().to_string();
+	let parent = |r: &Value| r.get("parentId").and_then(|x| x.as_str()).unwrap_or_default().to_string();
+
+	let workspaces: Vec<String> = resources.iter().filter(|r| kind(r) == "workspace").map(id).collect();
+
+	let mut root = PostWomanCollection::default();
+
+	// base environments belong to workspace, sub environments to base ones
+	for env in resources.iter().filter(|r| kind(r) == "environment") {
+		let name = env.get("name").and_then(|x| x.as_str()).unwrap_or("unnamed");
+		if !workspaces.contains(&parent(env)) {
+			eprintln!("! sub environment '{name}' is not supported, skipping it");
+		}
+	}
+
+	let urls: Vec<String> = resources.iter()
+		.filter(|r| kind(r) == "request")
+		.map(|r| convert(r.get("url").and_then(|x| x.as_str()).unwrap_or_default()))
+		.collect();
+	root.client = ClientConfig {
+		base: super::common_base(urls.iter().map(|x| x.as_str())),
+		..Default::default()
+	};
+
+	let build = Build { resources: &resources, base: root.client.base.as_deref() };
+	let mut top = ImportedFolder::default();
+	for workspace in resources.iter().filter(|r| kind(r) == "workspace") {
+		let name = workspace.get("name").and_then(|x| x.as_str()).unwrap_or("workspace");
+		let mut folder = build.folder(name, &id(workspace));
+		for env in resources.iter().filter(|r| kind(r) == "environment" && parent(r) == id(workspace)) {
+			insert_data(&mut folder.env, env);
+		}
+		if workspaces.len() == 1 {
+			top = folder;
+		} else {
+			// routes of different workspaces would clash, keep each one in its own file
+			top.folders.push(folder);
+		}
+	}
+	for other in resources.iter().filter(|r| kind(r) != "workspace" && !workspaces.contains(&parent(r))) {
+		if kind(other) == "request" && !resources.iter().any(|r| id(r) == parent(other)) {
+			eprintln!("! request '{}' has no parent workspace, skipping it", other.get("name").and_then(|x| x.as_str()).unwrap_or("unnamed"));
+		}
+	}
+	root.env = top.env;
+	root.route = top.routes;
+
+	Ok((root, top.folders))
+}
+
+fn insert_data(env: &mut toml::Table, resource: &Value) {
+	for (k, v) in resource.get("data").and_then(|x| x.as_object()).into_iter().flatten() {
+		let value = match v {
+			Value::String(s) => Some(toml::Value::String(convert(s))),
+			other => super::json_to_toml(other),
+		};
+		match value {
+			Some(v) => { env.insert(k.clone(), v); },
+			None => eprintln!("! variable '{k}' has no representable value, skipping it"),
+		}
+	}
+}
+
+struct Build<'a> {
+	resources: &'a [Value],
+	base: Option<&'a str>,
+}
+
+impl Build<'_> {
+	fn folder(&self, name: &str, id: &str) -> ImportedFolder {
+		let mut out = ImportedFolder { name: name.to_string(), ..Default::default() };
+
+		for child in self.resources.iter().filter(|r| r.get("parentId").and_then(|x| x.as_str()) == Some(id)) {
+			let child_name = child.get("name").and_then(|x| x.as_str()).unwrap_or("unnamed");
+			let child_id = child.get("_id").and_then(|x| x.as_str()).unwrap_or_default();
+			match child.get("_type").and_then(|x| x.as_str()) {
+				Some("request_group") => {
+					let mut folder = self.folder(child_name, child_id);
+					insert_data(&mut folder.env, child.get("environment").map(|x| serde_json::json!({ "data": x })).as_ref().unwrap_or(&Value::Null));
+					out.folders.push(folder);
+				},
+				Some("request") => {
+					let route = super::unique_name(&super::slug(child_name), |n| out.routes.contains_key(n));
+					out.routes.insert(route, self.endpoint(child_name, child));
+				},
+				Some("environment") | Some("cookie_jar") | Some("api_spec") => {},
+				Some(other) => eprintln!("! {child_name}: '{other}' resources are not supported, skipping it"),
+				None => {},
+			}
+		}
+
+		out
+	}
+
+	fn endpoint(&self, name: &str, request: &Value) -> EndpointConfig {
+		for script in ["preRequestScript", "afterResponseScript"] {
+			if request.get(script).and_then(|x| x.as_str()).is_some_and(|x| !x.trim().is_empty()) {
+				eprintln!("! {name}: {script} is not supported, dropping it");
+			}
+		}
+
+		let url = convert(request.get("url").and_then(|x| x.as_str()).unwrap_or_default());
+		let (path, absolute, query) = super::split_url(&url, self.base);
+		let mut query = query.unwrap_or_default();
+		query.extend(pairs(request.get("parameters")).into_iter().map(|(k, v)| format!("{k}={v}")));
+
+		let mut headers: Vec<String> = pairs(request.get("headers"))
+			.into_iter()
+			.map(|(k, v)| format!("{k}: {v}"))
+			.collect();
+
+		let auth = request.get("authentication");
+		let field = |key: &str| auth.and_then(|x| x.get(key)).and_then(|x| x.as_str()).map(convert);
+		let auth = if auth.and_then(|x| x.get("disabled")).and_then(|x| x.as_bool()).unwrap_or(false) {
+			None // explicitly turned off
+		} else {
+			match field("type").as_deref() {
+				None | Some("none") => None,
+				Some("bearer") => {
+					let prefix = field("prefix").filter(|x| !x.is_empty());
+					match prefix {
+						// custom prefixes can only be expressed as plain header
+						Some(prefix) if !prefix.eq_ignore_ascii_case("bearer") => {
+							headers.push(format!("Authorization: {prefix} {}", field("token").unwrap_or_default()));
+							None
+						},
+						_ => Some(AuthConfig::Bearer { token: field("token").unwrap_or_default() }),
+					}
+				},
+				Some("basic") => Some(AuthConfig::Basic {
+					username: field("username").unwrap_or_default(),
+					password: field("password"),
+				}),
+				Some("digest") => Some(AuthConfig::Digest {
+					username: field("username").unwrap_or_default(),
+					password: field("password").unwrap_or_default(),
+				}),
+				Some("apikey") => Some(AuthConfig::ApiKey {
+					key: field("key").unwrap_or_default(),
+					value: field("value").unwrap_or_default(),
+					location: match field("addTo").as_deref() {
+						Some("queryParams") => Some(ApiKeyLocation::Query),
+						_ => None,
+					},
+				}),
+				Some(other) => {
+					eprintln!("! {name}: '{other}' auth is not supported, skipping it");
+					None
+				},
+			}
+		};
+
+		let mut body = None;
+		let raw_body = request.get("body");
+		let mime = raw_body.and_then(|x| x.get("mimeType")).and_then(|x| x.as_str());
+		let text = raw_body.and_then(|x| x.get("text")).and_then(|x| x.as_str()).map(convert);
+		match mime {
+			None => body = text.and_then(|x| super::json_body(&x)),
+			Some("application/x-www-form-urlencoded") => {
+				let fields: Vec<String> = pairs(raw_body.and_then(|x| x.get("params")))
+					.into_iter()
+					// don't encode our variables, they're filled later
+					.map(|(k, v)| if v.contains("${") { format!("{}={v}", urlencode(&k)) } else { format!("{}={}", urlencode(&k), urlencode(&v)) })
+					.collect();
+				body = Some(StringOr::Str(fields.join("&")));
+			},
+			Some("multipart/form-data") => eprintln!("! {name}: multipart body is not supported, skipping it"),
+			Some(m) if m.contains("json") => body = text.and_then(|x| super::json_body(&x)),
+			Some(_) => body = text.map(StringOr::Str),
+		}
+
+		if let Some(mime) = mime {
+			if body.is_some() && !headers.iter().any(|h| h.to_lowercase().starts_with("content-type:")) {
+				headers.push(format!("Content-Type: {mime}"));
+			}
+		}
+
+		let method = request.get("method").and_then(|x| x.as_str()).unwrap_or("GET").to_uppercase();
+
+		EndpointConfig {
+			path,
+			absolute,
+			method: if method == "GET" { None } else { Some(method) },
+			query: if query.is_empty() { None } else { Some(query) },
+			headers: if headers.is_empty() { None } else { Some(headers) },
+			body,
+			auth,
+			..Default::default()
+		}
+	}
+}
+
+/// enabled name/value pairs, as insomnia stores headers, parameters and form fields
+fn pairs(list: Option<&Value>) -> Vec<(String, String)> {
+	list.and_then(|x| x.as_array())
+		.into_iter()
+		.flatten()
+		.filter(|p| !p.get("disabled").and_then(|x| x.as_bool()).unwrap_or(false))
+		.filter_map(|p| Some((convert(p.get("name")?.as_str()?), convert(p.get("value")?.as_str()?))))
+		.collect()
+}
+
+/// like templates conversion, but also warn about insomnia tags, which we can't replicate
+fn convert(s: &str) -> String {
+	if s.contains("{%") {
+		eprintln!("! template tags are not supported, leaving them as is: {s}");
+	}
+	convert_template(s)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::model::BodyConfig;
+
+	const FIXTURE: &str = r##"{
+		"__export_format": 4,
+		"resources": [
+			{ "_id": "wrk_1", "_type": "workspace", "name": "api" },
+			{ "_id": "env_1", "_type": "environment", "parentId": "wrk_1", "data": { "base": "https://example.com", "retries": 3 } },
+			{ "_id": "env_2", "_type": "environment", "parentId": "env_1", "data": { "base": "https://staging.example.com" } },
+			{ "_id": "req_1", "_type": "request", "parentId": "wrk_1", "name": "List Users", "method": "GET",
+				"url": "{{ _.base }}/users?page=1", "parameters": [ { "name": "size", "value": "{{size}}" }, { "name": "off", "value": "x", "disabled": true } ] },
+			{ "_id": "fld_1", "_type": "request_group", "parentId": "wrk_1", "name": "Users", "environment": { "user": "admin" } },
+			{ "_id": "req_2", "_type": "request", "parentId": "fld_1", "name": "Create User", "method": "POST", "url": "{{ _.base }}/users",
+				"body": { "mimeType": "application/json", "text": "{\"name\": \"{{ _.user }}\"}" } },
+			{ "_id": "req_3", "_type": "request", "parentId": "fld_1", "name": "Login", "method": "post", "url": "{{ _.base }}/login",
+				"body": { "mimeType": "application/x-www-form-urlencoded", "params": [ { "name": "user", "value": "{{ _.user }}" }, { "name": "pass", "value": "a&b" } ] } },
+			{ "_id": "req_4", "_type": "request", "parentId": "fld_1", "name": "Note", "method": "PUT", "url": "{{ _.base }}/note",
+				"headers": [ { "name": "Content-Type", "value": "text/markdown" } ], "body": { "mimeType": "text/plain", "text": "# hi" } }
+		]
+	}"##;
+
+	#[test]
+	fn rejects_other_export_formats() {
+		assert!(collection(r#"{ "__export_format": 3, "resources": [] }"#).is_err());
+	}
+
+	#[test]
+	fn nests_request_groups_with_their_environment() {
+		let (root, folders) = collection(FIXTURE).expect("valid export");
+		assert_eq!(root.client.base.as_deref(), Some("${base}"));
+		// sub environments are skipped, base one lands in root env
+		assert_eq!(root.env.get("base").and_then(|x| x.as_str()), Some("https://example.com"));
+		assert_eq!(root.env.get("retries").and_then(|x| x.as_integer()), Some(3));
+
+		assert_eq!(root.route.keys().collect::<Vec<_>>(), ["list-users"]);
+		let list = &root.route["list-users"];
+		assert_eq!(list.path, "/users");
+		assert_eq!(list.query, Some(vec!["page=1".to_string(), "size=${size}".to_string()]));
+
+		assert_eq!(folders.len(), 1);
+		assert_eq!(folders[0].name, "Users");
+		assert_eq!(folders[0].env.get("user").and_then(|x| x.as_str()), Some("admin"));
+		assert_eq!(folders[0].routes.keys().collect::<Vec<_>>(), ["create-user", "login", "note"]);
+	}
+
+	#[test]
+	fn keeps_workspaces_apart() {
+		let raw = r#"{ "__export_format": 4, "resources": [
+			{ "_id": "wrk_1", "_type": "workspace", "name": "Billing" },
+			{ "_id": "wrk_2", "_type": "workspace", "name": "Users" },
+			{ "_id": "env_1", "_type": "environment", "parentId": "wrk_1", "data": { "base": "https://billing.example.com" } },
+			{ "_id": "env_2", "_type": "environment", "parentId": "wrk_2", "data": { "base": "https://users.example.com" } },
+			{ "_id": "req_1", "_type": "request", "parentId": "wrk_1", "name": "health", "url": "{{ _.base }}/health" },
+			{ "_id": "req_2", "_type": "request", "parentId": "wrk_2", "name": "health", "url": "{{ _.base }}/health" }
+		] }"#;
+		let (root, folders) = collection(raw).expect("valid export");
+		assert!(root.route.is_empty());
+		assert!(root.env.is_empty());
+		assert_eq!(folders.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(), ["Billing", "Users"]);
+		for (folder, base) in folders.iter().zip(["https://billing.example.com", "https://users.example.com"]) {
+			assert_eq!(folder.env.get("base").and_then(|x| x.as_str()), Some(base));
+			assert_eq!(folder.routes["health"].path, "/health");
+		}
+	}
+
+	#[test]
+	fn converts_bodies_by_mime_type() {
+		let (_, folders) = collection(FIXTURE).expect("valid export");
+		let routes = &folders[0].routes;
+
+		let create = &routes["create-user"];
+		assert_eq!(create.headers, Some(vec!["Content-Type: application/json".to_string()]));
+		match &create.body {
+			Some(StringOr::T(BodyConfig::Json(table))) => assert_eq!(table.get("name").and_then(|x| x.as_str()), Some("${user}")),
+			other => panic!("expected json body, got {other:?}"),
+		}
+
+		let login = &routes["login"];
+		assert_eq!(login.method.as_deref(), Some("POST"));
+		assert_eq!(login.headers, Some(vec!["Content-Type: application/x-www-form-urlencoded".to_string()]));
+		assert!(matches!(login.body, Some(StringOr::Str(ref x)) if x == "user=${user}&pass=a%26b"));
+
+		// existing header is kept as is
+		let note = &routes["note"];
+		assert_eq!(note.headers, Some(vec!["Content-Type: text/markdown".to_string()]));
+		assert!(matches!(note.body, Some(StringOr::Str(ref x)) if x == "# hi"));
+	}
+
+	#[test]
+	fn maps_authentication() {
+		let raw = r#"{ "__export_format": 4, "resources": [
+			{ "_id": "wrk_1", "_type": "workspace", "name": "api" },
+			{ "_id": "req_1", "_type": "request", "parentId": "wrk_1", "name": "token", "url": "https://example.com",
+				"authentication": { "type": "bearer", "token": "{{ _.token }}" } },
+			{ "_id": "req_2", "_type": "request", "parentId": "wrk_1", "name": "custom", "url": "https://example.com",
+				"authentication": { "type": "bearer", "prefix": "Token", "token": "x" } },
+			{ "_id": "req_3", "_type": "request", "parentId": "wrk_1", "name": "off", "url": "https://example.com",
+				"authentication": { "type": "basic", "username": "u", "disabled": true } }
+		] }"#;
+		let (root, _) = collection(raw).expect("valid export");
+		assert!(matches!(root.route["token"].auth, Some(AuthConfig::Bearer { ref token }) if token == "${token}"));
+		assert!(root.route["custom"].auth.is_none());
+		assert_eq!(root.route["custom"].headers, Some(vec!["Authorization: Token x".to_string()]));
+		assert!(root.route["off"].auth.is_none());
+	}
+}
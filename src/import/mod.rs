@@ -0,0 +1,277 @@
+pub mod curl;
+pub mod har;
+pub mod insomnia;
+pub mod openapi;
+pub mod postman;
+
+use indexmap::IndexMap;
+
+use crate::ext::StringOr;
+use crate::model::{BodyConfig, EndpointConfig};
+use crate::{PostWomanCollection, PostWomanError};
+
+/// folder of imported routes, each one will become an included collection
+#[derive(Debug, Default)]
+pub struct ImportedFolder {
+	pub name: String,
+	pub env: toml::Table,
+	pub routes: IndexMap<String, EndpointConfig>,
+	pub folders: Vec<ImportedFolder>,
+}
+
+/// pretty print given collection as toml, leaving out empty sections
+pub fn collection_toml(collection: &PostWomanCollection) -> Result<String, PostWomanError> {
+	#[derive(serde::Serialize)]
+	struct Collection<'a> {
+		#[serde(skip_serializing_if = "Vec::is_empty")]
+		include: &'a Vec<String>,
+		#[serde(skip_serializing_if = "toml::Table::is_empty")]
+		env: &'a toml::Table,
+		#[serde(skip_serializing_if = "toml::Table::is_empty")]
+		client: toml::Table,
+		route: &'a IndexMap<String, EndpointConfig>,
+	}
+
+	let mut doc: toml_edit::DocumentMut = toml_edit::ser::to_string_pretty(&Collection {
+		include: &collection.include,
+		env: &collection.env,
+		client: toml::Table::try_from(&collection.client)
+			.map_err(|e| PostWomanError::ImportError(format!("invalid client config: {e}")))?,
+		route: &collection.route,
+	})?
+		.parse()
+		.expect("toml_edit can't parse its own output???");
+
+	// bodies are more readable as inline tables, and closer to their json counterpart
+	if let Some(routes) = doc.get_mut("route").and_then(|x| x.as_table_mut()) {
+		routes.set_implicit(true);
+		for (name, endpoint) in &collection.route {
+			let Some(StringOr::T(ref body)) = endpoint.body else { continue };
+			let Some(table) = routes.get_mut(name).and_then(|x| x.as_table_like_mut()) else { continue };
+			let Ok(body) = toml::Value::try_from(body) else { continue };
+			let inline: toml_edit::Value = body
+				.to_string()
+				.parse()
+				.expect("toml_edit can't parse toml output???");
+			table.remove("body");
+			table.insert("body", toml_edit::Item::Value(inline));
+		}
+	}
+
+	Ok(doc.to_string())
+}
+
+/// write imported collection, merging it into existing one if present
+/// existing includes, env and client options are kept, conflicting route names get renamed
+pub fn save(path: &std::path::Path, mut imported: PostWomanCollection, print: bool) -> Result<(), PostWomanError> {
+	let existing = match std::fs::read_to_string(path) {
+		Ok(raw) => Some(raw),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+		Err(e) => return Err(e.into()),
+	};
+
+	if let Some(ref raw) = existing {
+		let current: PostWomanCollection = toml::from_str(raw)?;
+		let mut routes = IndexMap::new();
+		for (name, endpoint) in imported.route {
+			let name = unique_name(&name, |n| current.route.contains_key(n) || routes.contains_key(n));
+			routes.insert(name, endpoint);
+		}
+		imported.route = routes;
+	}
+
+	let snippet = collection_toml(&imported)?;
+
+	if print {
+		println!("# {}\n{snippet}", path.display());
+		return Ok(());
+	}
+
+	let out = match existing {
+		Some(raw) => merge_toml(&raw, &snippet)?,
+		None => {
+			if let Some(parent) = path.parent() {
+				std::fs::create_dir_all(parent)?;
+			}
+			snippet
+		},
+	};
+
+	std::fs::write(path, out)?;
+
+	for name in imported.route.keys() {
+		eprintln!(" + imported route '{name}' into {path:?}");
+	}
+
+	Ok(())
+}
+
+/// write imported collection and its folders, each folder gets its own file included by parent
+/// folder files are placed in a directory named after parent collection
+pub fn save_tree(path: &std::path::Path, mut collection: PostWomanCollection, folders: Vec<ImportedFolder>, print: bool) -> Result<(), PostWomanError> {
+	let dir = path.parent().map(|x| x.to_path_buf()).unwrap_or_default();
+	let stem = path.file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+
+	let mut children: Vec<(String, std::path::PathBuf, ImportedFolder)> = Vec::new();
+	for folder in folders {
+		let name = unique_name(&slug(&folder.name), |n| children.iter().any(|(x, _, _)| x == n));
+		let include = format!("{stem}/{name}.toml");
+		collection.include.push(include.clone());
+		children.push((name, dir.join(include), folder));
+	}
+
+	let client = collection.client.clone();
+	save(path, collection, print)?;
+
+	for (_name, child_path, folder) in children {
+		// client options are not inherited by includes, only env is
+		let child = PostWomanCollection {
+			client: client.clone(),
+			env: folder.env,
+			route: folder.routes,
+			..Default::default()
+		};
+		save_tree(&child_path, child, folder.folders, print)?;
+	}
+
+	Ok(())
+}
+
+fn merge_toml(raw: &str, snippet: &str) -> Result<String, PostWomanError> {
+	let mut doc: toml_edit::DocumentMut = raw.parse()
+		.map_err(|e| PostWomanError::ImportError(format!("invalid existing collection: {e}")))?;
+	let new: toml_edit::DocumentMut = snippet.parse().expect("toml_edit can't parse its own output???");
+
+	for (key, item) in new.iter() {
+		if let Some(includes) = item.as_array() {
+			let current = doc.entry(key)
+				.or_insert(toml_edit::value(toml_edit::Array::new()))
+				.as_array_mut()
+				.ok_or_else(|| PostWomanError::ImportError(format!("existing '{key}' is not an array")))?;
+			for include in includes {
+				if !current.iter().any(|x| x.as_str() == include.as_str()) {
+					current.push(include.clone());
+				}
+			}
+		} else if let Some(table) = item.as_table_like() {
+			let current = doc.entry(key)
+				.or_insert(toml_edit::table())
+				.as_table_like_mut()
+				.ok_or_else(|| PostWomanError::ImportError(format!("existing '{key}' is not a table")))?;
+			for (k, v) in table.iter() {
+				if current.get(k).is_none() {
+					current.insert(k, v.clone());
+				}
+			}
+		}
+	}
+
+	Ok(doc.to_string())
+}
+
+/// if all urls start with the same variable, it's most likely a base url
+pub fn common_base<'a>(mut urls: impl Iterator<Item = &'a str>) -> Option<String> {
+	let first = urls.next()?;
+	let base = first.strip_prefix("${")
+		.and_then(|x| x.split_once('}'))
+		.map(|(var, _)| format!("${{{var}}}"))?;
+	if urls.all(|x| x.starts_with(&base)) {
+		Some(base)
+	} else {
+		None
+	}
+}
+
+/// json body from other tools: objects become toml tables, anything else is kept as string
+pub fn json_body(data: &str) -> Option<StringOr<BodyConfig>> {
+	if data.is_empty() {
+		return None;
+	}
+	match serde_json::from_str::<serde_json::Value>(data) {
+		Ok(serde_json::Value::Object(obj)) => match toml::Table::try_from(obj) {
+			Ok(table) => Some(StringOr::T(BodyConfig::Json(table))),
+			Err(_) => Some(StringOr::Str(data.to_string())), // null values can't be represented in toml
+		},
+		_ => Some(StringOr::Str(data.to_string())),
+	}
+}
+
+/// convert json values into toml ones, None if they contain nulls which toml can't represent
+pub fn json_to_toml(value: &serde_json::Value) -> Option<toml::Value> {
+	toml::Value::try_from(value).ok()
+}
+
+/// split an url into path and query parameters, stripping base if it matches
+pub fn split_url(url: &str, base: Option<&str>) -> (String, Option<bool>, Option<Vec<String>>) {
+	let (path, query) = match url.split_once('?') {
+		Some((path, query)) => (path, Some(query.split('&').map(|x| x.to_string()).collect())),
+		None => (url, None),
+	};
+
+	// base must end on a segment boundary, `/api` shouldn't match `/apiary`
+	let relative = base.and_then(|base| path.strip_prefix(base).filter(|x| x.is_empty() || x.starts_with('/') || base.ends_with('/')));
+	match relative {
+		Some(relative) => (relative.to_string(), None, query),
+		None => (path.to_string(), Some(true), query),
+	}
+}
+
+/// make given name unique among existing ones, appending a counter if necessary
+pub fn unique_name(name: &str, exists: impl Fn(&str) -> bool) -> String {
+	if !exists(name) {
+		return name.to_string();
+	}
+	let mut i = 2;
+	while exists(&format!("{name}-{i}")) {
+		i += 1;
+	}
+	format!("{name}-{i}")
+}
+
+/// derive a route name from method and last meaningful path segment
+pub fn route_name(method: &str, path: &str) -> String {
+	let segment = path
+		.split('/')
+		.rfind(|x| !x.is_empty() && !x.contains(':') && !x.starts_with("${"))
+		.unwrap_or("root");
+	format!("{}-{}", method.to_lowercase(), slug(segment))
+}
+
+/// lowercase name with only alphanumerics, dashes and underscores, safe as toml key and file name
+pub fn slug(name: &str) -> String {
+	let slug = name
+		.chars()
+		.map(|c| if c.is_ascii_alphanumeric() || c == '_' { c.to_ascii_lowercase() } else { '-' })
+		.collect::<String>();
+	let slug = slug.split('-').filter(|x| !x.is_empty()).collect::<Vec<_>>().join("-");
+	if slug.is_empty() { "unnamed".to_string() } else { slug }
+}
+
+/// convert `{{var}}` templates used by other tools (also insomnia's `{{ _.var }}`) into our `${var}` ones
+pub fn convert_template(s: &str) -> String {
+	let pattern = regex::Regex::new(r"\{\{\s*(?:_\.)?([^{}\s]+)\s*\}\}").expect("hardcoded regex is invalid");
+	pattern.replace_all(s, |c: &regex::Captures| match &c[1] {
+		"$timestamp" => "${POSTWOMAN_TIMESTAMP}".to_string(),
+		var => {
+			if var.starts_with('$') {
+				eprintln!("! dynamic variable '{var}' is not supported, set it in env");
+			}
+			format!("${{{var}}}")
+		},
+	}).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn strips_base_only_on_segment_boundaries() {
+		assert_eq!(split_url("https://x.dev/api/users?a=1", Some("https://x.dev/api")), ("/users".to_string(), None, Some(vec!["a=1".to_string()])));
+		assert_eq!(split_url("https://x.dev/api?a=1", Some("https://x.dev/api")), ("".to_string(), None, Some(vec!["a=1".to_string()])));
+		assert_eq!(split_url("https://x.dev/api/users", Some("https://x.dev/api/")).0, "users");
+		assert_eq!(split_url("https://x.dev/apiary", Some("https://x.dev/api")), ("https://x.dev/apiary".to_string(), Some(true), None));
+		assert_eq!(split_url("${base_v2}/users", Some("${base}")), ("${base_v2}/users".to_string(), Some(true), None));
+		assert_eq!(split_url("${base}/users", None), ("${base}/users".to_string(), Some(true), None));
+	}
+}
@@ -0,0 +1,295 @@
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use crate::ext::{stringify_json, urlencode, StringOr};
+use crate::model::{BodyConfig, EndpointConfig};
+use crate::PostWomanError;
+
+const METHODS: &[&str] = &["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+// max chain of `$ref`s to follow, in case of reference loops
+const MAX_REF_DEPTH: usize = 8;
+
+/// parse an OpenAPI 3 document, either json or yaml
+pub fn parse(raw: &str) -> Result<Value, PostWomanError> {
+	let spec: Value = match serde_json::from_str(raw) {
+		Ok(x) => x,
+		Err(_) => serde_yaml::from_str(raw)
+			.map_err(|e| PostWomanError::ImportError(format!("spec is neither valid json nor yaml: {e}")))?,
+	};
+
+	match spec.get("openapi").and_then(|x| x.as_str()) {
+		Some(v) if v.starts_with('3') => Ok(spec),
+		Some(v) => Err(PostWomanError::ImportError(format!("unsupported OpenAPI version {v}, only 3.x is supported"))),
+		None => Err(PostWomanError::ImportError("not an OpenAPI 3 document (Swagger 2 is not supported)".to_string())),
+	}
+}
+
+/// first server url, with its variables replaced by their defaults
+pub fn server(spec: &Value) -> Option<String> {
+	let server = spec.get("servers")?.get(0)?;
+	let mut url = server.get("url")?.as_str()?.trim_end_matches('/').to_string();
+	if let Some(Value::Object(vars)) = server.get("variables") {
+		for (k, v) in vars {
+			if let Some(default) = v.get("default") {
+				url = url.replace(&format!("{{{k}}}"), &stringify_json(default));
+			}
+		}
+	}
+	Some(url)
+}
+
+/// one route for each operation in spec, paths are relative to server unless base differs
+pub fn routes(spec: &Value, base: Option<&str>) -> IndexMap<String, EndpointConfig> {
+	let server = server(spec);
+	let absolute = server.is_some() && server.as_deref() != base;
+	let mut routes = IndexMap::new();
+
+	let Some(Value::Object(paths)) = spec.get("paths") else { return routes };
+
+	for (path, item) in paths {
+		let item = resolve(spec, item);
+		let shared_params = item.get("parameters").and_then(|x| x.as_array());
+
+		for method in METHODS {
+			let Some(op) = item.get(*method) else { continue };
+
+			// operation parameters override path ones with same name and location
+			let mut params: Vec<&Value> = Vec::new();
+			let op_params = op.get("parameters").and_then(|x| x.as_array());
+			for param in shared_params.into_iter().chain(op_params).flatten().map(|x| resolve(spec, x)) {
+				let key = |p: &Value| (p.get("name").cloned(), p.get("in").cloned());
+				match params.iter().position(|x| key(x) == key(param)) {
+					Some(i) => params[i] = param,
+					None => params.push(param),
+				}
+			}
+
+			let mut endpoint = EndpointConfig {
+				method: if *method == "get" { None } else { Some(method.to_uppercase()) },
+				..Default::default()
+			};
+
+			let mut filled = path.clone();
+			let mut query = Vec::new();
+			let mut headers = Vec::new();
+			for param in params {
+				let Some(name) = param.get("name").and_then(|x| x.as_str()) else { continue };
+				let location = param.get("in").and_then(|x| x.as_str());
+				let example = param_example(spec, param);
+				// path params are always required, optional ones are only worth sending with a meaningful value
+				let required = location == Some("path") || param.get("required").and_then(|x| x.as_bool()).unwrap_or(false);
+				if example.is_none() && !required {
+					continue;
+				}
+				match (location, example) {
+					(Some("path"), Some(value)) => filled = filled.replace(&format!("{{{name}}}"), &urlencode(&value)),
+					(Some("query"), Some(value)) => query.push(format!("{}={}", urlencode(name), urlencode(&value))),
+					(Some("query"), None) => query.push(format!("{}=${{{name}}}", urlencode(name))),
+					(Some("header"), value) => headers.push(format!("{name}: {}", value.unwrap_or_else(|| format!("${{{name}}}")))),
+					_ => {}, // path params without example stay templates, cookies are managed by client
+				}
+			}
+
+			// path templates use the same braces as our variables, just without the dollar
+			endpoint.path = filled.replace('{', "${");
+			if absolute {
+				endpoint.path = format!("{}{}", server.as_deref().unwrap_or_default(), endpoint.path);
+				endpoint.absolute = Some(true);
+			}
+
+			if let Some(body) = op.get("requestBody").map(|x| resolve(spec, x)) {
+				if let Some(json) = body.get("content").and_then(|c| c.get("application/json")) {
+					headers.push("Content-Type: application/json".to_string());
+					let example = json.get("example").cloned()
+						.or_else(|| json.get("examples")
+							.and_then(|x| x.as_object())
+							.and_then(|x| x.values().next())
+							.map(|x| resolve(spec, x))
+							.and_then(|x| x.get("value").cloned())
+						)
+						.or_else(|| json.get("schema").map(|s| example_from_schema(spec, s, &mut Vec::new())));
+					endpoint.body = example.map(|x| match x {
+						Value::Object(obj) => toml::Table::try_from(obj.clone())
+							.map(|x| StringOr::T(BodyConfig::Json(x)))
+							.unwrap_or_else(|_| StringOr::Str(Value::Object(obj).to_string())),
+						other => StringOr::Str(other.to_string()),
+					});
+				}
+			}
+
+			// first successful status code, 200 is already the default
+			endpoint.status = op.get("responses")
+				.and_then(|x| x.as_object())
+				.and_then(|x| x.keys().filter_map(|k| k.parse::<u16>().ok()).filter(|k| (200..300).contains(k)).min())
+				.filter(|x| *x != 200);
+
+			if !query.is_empty() { endpoint.query = Some(query) };
+			if !headers.is_empty() { endpoint.headers = Some(headers) };
+
+			let name = op.get("operationId")
+				.and_then(|x| x.as_str())
+				.map(|x| x.to_string())
+				// named after templates, examples make poor names
+				.unwrap_or_else(|| super::route_name(method, &path.replace('{', "${")));
+			let name = super::unique_name(&name, |n| routes.contains_key(n));
+			routes.insert(name, endpoint);
+		}
+	}
+
+	routes
+}
+
+/// parameter value from its examples or its schema, if any is given
+fn param_example(spec: &Value, param: &Value) -> Option<String> {
+	let schema = param.get("schema").map(|s| resolve(spec, s));
+	param.get("example")
+		.or_else(|| param.get("examples")
+			.and_then(|x| x.as_object())
+			.and_then(|x| x.values().next())
+			.map(|x| resolve(spec, x))
+			.and_then(|x| x.get("value"))
+		)
+		.or_else(|| schema.and_then(|s| s.get("example")))
+		.or_else(|| schema.and_then(|s| s.get("default")))
+		.map(stringify_json)
+}
+
+/// follow local `$ref`s, anything else is returned as is
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+	let mut value = value;
+	for _ in 0..MAX_REF_DEPTH {
+		let Some(reference) = value.get("$ref").and_then(|x| x.as_str()) else { break };
+		let Some(pointer) = reference.strip_prefix('#') else { break };
+		match spec.pointer(pointer) {
+			Some(x) => value = x,
+			None => break,
+		}
+	}
+	value
+}
+
+/// build a plausible payload from a json schema, preferring provided examples and defaults
+/// recursive schemas are expanded only once, keeping track of references being visited
+fn example_from_schema<'a>(spec: &'a Value, schema: &'a Value, visiting: &mut Vec<&'a str>) -> Value {
+	let reference = schema.get("$ref").and_then(|x| x.as_str());
+	if let Some(reference) = reference {
+		if visiting.contains(&reference) {
+			return Value::Null;
+		}
+		visiting.push(reference);
+	}
+	let out = example_from_resolved_schema(spec, resolve(spec, schema), visiting);
+	if reference.is_some() {
+		visiting.pop();
+	}
+	out
+}
+
+fn example_from_resolved_schema<'a>(spec: &'a Value, schema: &'a Value, visiting: &mut Vec<&'a str>) -> Value {
+	if let Some(x) = schema.get("example").or(schema.get("default")) {
+		return x.clone();
+	}
+	if let Some(Value::Array(options)) = schema.get("enum") {
+		if let Some(first) = options.first() {
+			return first.clone();
+		}
+	}
+	if let Some(Value::Array(all)) = schema.get("allOf") {
+		let mut out = serde_json::Map::new();
+		for sub in all {
+			if let Value::Object(obj) = example_from_schema(spec, sub, visiting) {
+				out.extend(obj);
+			}
+		}
+		return Value::Object(out);
+	}
+	if let Some(Value::Array(any)) = schema.get("oneOf").or(schema.get("anyOf")) {
+		if let Some(first) = any.first() {
+			return example_from_schema(spec, first, visiting);
+		}
+	}
+
+	match schema.get("type").and_then(|x| x.as_str()) {
+		Some("string") => Value::String(String::new()),
+		Some("integer") | Some("number") => Value::from(0),
+		Some("boolean") => Value::Bool(false),
+		Some("array") => match schema.get("items") {
+			Some(items) => Value::Array(vec![example_from_schema(spec, items, visiting)]),
+			None => Value::Array(Vec::new()),
+		},
+		_ => match schema.get("properties") {
+			Some(Value::Object(props)) => Value::Object(
+				props.iter()
+					.map(|(k, v)| (k.clone(), example_from_schema(spec, v, visiting)))
+					.filter(|(_, v)| !v.is_null()) // can't be represented in toml
+					.collect()
+			),
+			_ => Value::Null,
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn operation_parameters_override_path_ones() {
+		let spec = parse(r##"{
+			"openapi": "3.0.0",
+			"paths": {
+				"/items": {
+					"parameters": [
+						{ "name": "limit", "in": "query", "example": 10 },
+						{ "name": "X-Trace", "in": "header", "example": "path" }
+					],
+					"get": {
+						"parameters": [
+							{ "name": "limit", "in": "query", "example": 50 },
+							{ "name": "limit", "in": "header", "example": "other" },
+							{ "$ref": "#/components/parameters/page" }
+						]
+					}
+				}
+			},
+			"components": { "parameters": { "page": { "name": "page", "in": "query" } } }
+		}"##).expect("valid spec");
+
+		let routes = routes(&spec, None);
+		let endpoint = routes.values().next().expect("one route");
+		// optional page has nothing to send
+		assert_eq!(endpoint.query, Some(vec!["limit=50".to_string()]));
+		assert_eq!(endpoint.headers, Some(vec!["X-Trace: path".to_string(), "limit: other".to_string()]));
+	}
+
+	#[test]
+	fn fills_parameters_from_examples_and_defaults() {
+		let spec = parse(r##"{
+			"openapi": "3.0.0",
+			"paths": {
+				"/users/{id}/posts/{post}": {
+					"get": {
+						"parameters": [
+							{ "name": "id", "in": "path", "example": "a b" },
+							{ "name": "post", "in": "path", "required": true },
+							{ "name": "q", "in": "query", "example": "x&y=z" },
+							{ "name": "sort", "in": "query", "schema": { "type": "string", "default": "new" } },
+							{ "name": "cursor", "in": "query", "required": true, "schema": { "type": "string" } },
+							{ "name": "debug", "in": "query", "schema": { "type": "boolean" } },
+							{ "name": "X-Tenant", "in": "header", "required": true },
+							{ "name": "X-Debug", "in": "header" }
+						]
+					}
+				}
+			}
+		}"##).expect("valid spec");
+
+		let routes = routes(&spec, None);
+		let (name, endpoint) = routes.first().expect("one route");
+		assert_eq!(name, "get-posts");
+		assert_eq!(endpoint.path, "/users/a%20b/posts/${post}");
+		assert_eq!(endpoint.query, Some(vec!["q=x%26y%3Dz".to_string(), "sort=new".to_string(), "cursor=${cursor}".to_string()]));
+		assert_eq!(endpoint.headers, Some(vec!["X-Tenant: ${X-Tenant}".to_string()]));
+	}
+}
@@ -0,0 +1,340 @@
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use crate::ext::urlencode;
+use crate::model::{ApiKeyLocation, AuthConfig, ClientConfig, EndpointConfig};
+use crate::{PostWomanCollection, PostWomanError};
+
+use super::{convert_template, ImportedFolder};
+
+/// parse a postman v2.1 collection, with an optional environment, into root collection and its folders
+pub fn collection(raw: &str, environment: Option<&str>) -> Result<(PostWomanCollection, Vec<ImportedFolder>), PostWomanError> {
+	let spec: Value = serde_json::from_str(raw)?;
+
+	let schema = spec.pointer("/info/schema").and_then(|x| x.as_str()).unwrap_or_default();
+	if !schema.contains("v2.1") {
+		eprintln!("! collection schema is not v2.1, import may be incomplete: {schema}");
+	}
+
+	let mut root = PostWomanCollection::default();
+
+	for var in spec.get("variable").and_then(|x| x.as_array()).into_iter().flatten() {
+		insert_var(&mut root.env, var, "key");
+	}
+
+	if let Some(environment) = environment {
+		let environment: Value = serde_json::from_str(environment)?;
+		for var in environment.get("values").and_then(|x| x.as_array()).into_iter().flatten() {
+			if var.get("enabled").and_then(|x| x.as_bool()).unwrap_or(true) {
+				insert_var(&mut root.env, var, "key");
+			}
+		}
+	}
+
+	report_events("collection", &spec);
+
+	let mut urls = Vec::new();
+	collect_urls(&spec, &mut urls);
+	root.client = ClientConfig {
+		base: super::common_base(urls.iter().map(|x| x.as_str())),
+		..Default::default()
+	};
+
+	let folder = folder("collection", &spec, root.client.base.as_deref(), spec.get("auth"));
+	root.route = folder.routes;
+
+	Ok((root, folder.folders))
+}
+
+pub(super) fn insert_var(env: &mut toml::Table, var: &Value, key: &str) {
+	let Some(k) = var.get(key).and_then(|x| x.as_str()) else { return };
+	match var.get("value").map(|v| match v {
+		Value::String(s) => Some(toml::Value::String(convert_template(s))),
+		other => super::json_to_toml(other),
+	}) {
+		Some(Some(v)) => { env.insert(k.to_string(), v); },
+		_ => eprintln!("! variable '{k}' has no representable value, skipping it"),
+	}
+}
+
+fn folder(name: &str, item: &Value, base: Option<&str>, auth: Option<&Value>) -> ImportedFolder {
+	let mut out = ImportedFolder { name: name.to_string(), ..Default::default() };
+
+	for child in item.get("item").and_then(|x| x.as_array()).into_iter().flatten() {
+		let child_name = child.get("name").and_then(|x| x.as_str()).unwrap_or("unnamed");
+		report_events(child_name, child);
+		let auth = child.get("auth").or(auth);
+		if child.get("item").is_some() {
+			out.folders.push(folder(child_name, child, base, auth));
+		} else if let Some(request) = child.get("request") {
+			let route = super::unique_name(&super::slug(child_name), |n| out.routes.contains_key(n));
+			out.routes.insert(route, endpoint(child_name, request, base, request.get("auth").or(auth)));
+		}
+	}
+
+	out
+}
+
+fn endpoint(name: &str, request: &Value, base: Option<&str>, auth: Option<&Value>) -> EndpointConfig {
+	let (path, absolute, query) = super::split_url(&url(request), base);
+	let query = query.unwrap_or_default();
+
+	let mut headers: Vec<String> = request.get("header")
+		.and_then(|x| x.as_array())
+		.into_iter()
+		.flatten()
+		.filter(|h| !h.get("disabled").and_then(|x| x.as_bool()).unwrap_or(false))
+		.filter_map(|h| Some(format!("{}: {}", h.get("key")?.as_str()?, h.get("value")?.as_str()?)))
+		.map(|h| convert_template(&h))
+		.collect();
+
+	let auth = match auth.and_then(|x| x.get("type")).and_then(|x| x.as_str()) {
+		None | Some("noauth") => None,
+		Some("bearer") => match auth_param(auth, "bearer", "token") {
+			Some(token) => Some(AuthConfig::Bearer { token }),
+			None => {
+				eprintln!("! {name}: bearer auth without token, skipping it");
+				None
+			},
+		},
+		Some("basic") => Some(AuthConfig::Basic {
+			username: auth_param(auth, "basic", "username").unwrap_or_default(),
+			password: auth_param(auth, "basic", "password"),
+		}),
+		Some("digest") => Some(AuthConfig::Digest {
+			username: auth_param(auth, "digest", "username").unwrap_or_default(),
+			password: auth_param(auth, "digest", "password").unwrap_or_default(),
+		}),
+		Some("apikey") => Some(AuthConfig::ApiKey {
+			key: auth_param(auth, "apikey", "key").unwrap_or_default(),
+			value: auth_param(auth, "apikey", "value").unwrap_or_default(),
+			location: match auth_param(auth, "apikey", "in").as_deref() {
+				Some("query") => Some(ApiKeyLocation::Query),
+				_ => None,
+			},
+		}),
+		Some(other) => {
+			eprintln!("! {name}: '{other}' auth is not supported, skipping it");
+			None
+		},
+	};
+
+	let mut body = None;
+	let raw_body = request.get("body");
+	match raw_body.and_then(|x| x.get("mode")).and_then(|x| x.as_str()) {
+		None => {},
+		Some("raw") => {
+			let raw = convert_template(raw_body.and_then(|x| x.get("raw")).and_then(|x| x.as_str()).unwrap_or_default());
+			// postman picks the header from the editor language when sending, it's never in exports
+			let language = raw_body.and_then(|x| x.pointer("/options/raw/language")).and_then(|x| x.as_str()).unwrap_or("text");
+			let content_type = match language {
+				"json" => "application/json",
+				"xml" => "application/xml",
+				"html" => "text/html",
+				"javascript" => "application/javascript",
+				_ => "text/plain",
+			};
+			if !raw.is_empty() && !headers.iter().any(|h| h.to_lowercase().starts_with("content-type:")) {
+				headers.push(format!("Content-Type: {content_type}"));
+			}
+			body = super::json_body(&raw);
+		},
+		Some("urlencoded") => {
+			let fields: Vec<String> = raw_body
+				.and_then(|x| x.get("urlencoded"))
+				.and_then(|x| x.as_array())
+				.into_iter()
+				.flatten()
+				.filter(|f| !f.get("disabled").and_then(|x| x.as_bool()).unwrap_or(false))
+				.filter_map(|f| Some((f.get("key")?.as_str()?, f.get("value")?.as_str()?)))
+				.map(|(k, v)| (convert_template(k), convert_template(v)))
+				// don't encode our variables, they're filled later
+				.map(|(k, v)| if v.contains("${") { format!("{}={v}", urlencode(&k)) } else { format!("{}={}", urlencode(&k), urlencode(&v)) })
+				.collect();
+			if !headers.iter().any(|h| h.to_lowercase().starts_with("content-type:")) {
+				headers.push("Content-Type: application/x-www-form-urlencoded".to_string());
+			}
+			body = Some(crate::ext::StringOr::Str(fields.join("&")));
+		},
+		Some("graphql") => {
+			let graphql = raw_body.and_then(|x| x.get("graphql"));
+			let query = graphql.and_then(|x| x.get("query")).and_then(|x| x.as_str()).unwrap_or_default();
+			let variables = graphql.and_then(|x| x.get("variables")).and_then(|x| x.as_str()).unwrap_or("{}");
+			let variables: Value = serde_json::from_str(variables).unwrap_or_default();
+			let payload = serde_json::json!({ "query": query, "variables": variables }).to_string();
+			body = super::json_body(&convert_template(&payload));
+			if !headers.iter().any(|h| h.to_lowercase().starts_with("content-type:")) {
+				headers.push("Content-Type: application/json".to_string());
+			}
+		},
+		Some(mode) => eprintln!("! {name}: '{mode}' body is not supported, skipping it"),
+	}
+
+	let method = request.get("method").and_then(|x| x.as_str()).unwrap_or("GET").to_uppercase();
+
+	EndpointConfig {
+		path,
+		absolute,
+		method: if method == "GET" { None } else { Some(method) },
+		query: if query.is_empty() { None } else { Some(query) },
+		headers: if headers.is_empty() { None } else { Some(headers) },
+		body,
+		auth,
+		..Default::default()
+	}
+}
+
+fn url(request: &Value) -> String {
+	let raw = match request.get("url") {
+		Some(Value::String(url)) => url.clone(),
+		Some(url) => url.get("raw").and_then(|x| x.as_str()).unwrap_or_default().to_string(),
+		None => String::new(),
+	};
+	convert_template(&raw)
+}
+
+fn collect_urls(item: &Value, urls: &mut Vec<String>) {
+	for child in item.get("item").and_then(|x| x.as_array()).into_iter().flatten() {
+		match child.get("request") {
+			Some(request) => urls.push(url(request)),
+			None => collect_urls(child, urls),
+		}
+	}
+}
+
+// v2.1 stores auth params as a list of key/value, v2.0 as a plain object
+fn auth_param(auth: Option<&Value>, kind: &str, key: &str) -> Option<String> {
+	let params = auth?.get(kind)?;
+	let value = match params {
+		Value::Array(params) => params.iter()
+			.find(|p| p.get("key").and_then(|x| x.as_str()) == Some(key))?
+			.get("value")?,
+		Value::Object(params) => params.get(key)?,
+		_ => return None,
+	};
+	Some(convert_template(&crate::ext::stringify_json(value)))
+}
+
+fn report_events(name: &str, item: &Value) {
+	let mut scripts: IndexMap<&str, usize> = IndexMap::new();
+	for event in item.get("event").and_then(|x| x.as_array()).into_iter().flatten() {
+		let listen = event.get("listen").and_then(|x| x.as_str()).unwrap_or("unknown");
+		let lines = match event.pointer("/script/exec") {
+			Some(Value::Array(lines)) => lines.iter().filter(|x| x.as_str().is_some_and(|x| !x.trim().is_empty())).count(),
+			Some(Value::String(s)) => s.lines().filter(|x| !x.trim().is_empty()).count(),
+			_ => 0,
+		};
+		if lines > 0 {
+			*scripts.entry(listen).or_default() += lines;
+		}
+	}
+	for (listen, lines) in scripts {
+		eprintln!("! {name}: {listen} script ({lines} lines) is not supported, dropping it");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ext::StringOr;
+	use crate::model::BodyConfig;
+
+	const FIXTURE: &str = r#"{
+		"info": { "name": "fixture", "schema": "https://schema.getpostman.com/json/collection/v2.1.0/collection.json" },
+		"variable": [ { "key": "base", "value": "https://example.com" }, { "key": "token", "value": "{{secret}}" } ],
+		"item": [
+			{ "name": "Get Users", "request": { "method": "GET", "url": { "raw": "{{base}}/users?page={{page}}" } } },
+			{ "name": "Users", "item": [
+				{ "name": "Create User", "request": {
+					"method": "POST",
+					"url": "{{base}}/users",
+					"body": { "mode": "raw", "raw": "{\"name\":\"{{name}}\"}", "options": { "raw": { "language": "json" } } }
+				} },
+				{ "name": "Admin", "item": [
+					{ "name": "Ban", "request": { "method": "delete", "url": "{{base}}/users/1" } }
+				] }
+			] }
+		]
+	}"#;
+
+	fn request(body: &str, headers: &str) -> EndpointConfig {
+		let raw = format!(r#"{{ "item": [ {{ "name": "x", "request": {{ "method": "POST", "url": "https://example.com", "header": {headers}, "body": {body} }} }} ] }}"#);
+		let (root, _) = collection(&raw, None).expect("valid collection");
+		root.route.into_values().next().expect("one route")
+	}
+
+	#[test]
+	fn nests_folders_and_strips_base() {
+		let (root, folders) = collection(FIXTURE, None).expect("valid collection");
+		assert_eq!(root.client.base.as_deref(), Some("${base}"));
+		assert_eq!(root.route.keys().collect::<Vec<_>>(), ["get-users"]);
+		assert_eq!(root.route["get-users"].path, "/users");
+		assert_eq!(root.route["get-users"].query, Some(vec!["page=${page}".to_string()]));
+
+		assert_eq!(folders.len(), 1);
+		assert_eq!(folders[0].name, "Users");
+		assert_eq!(folders[0].routes.keys().collect::<Vec<_>>(), ["create-user"]);
+		assert_eq!(folders[0].folders[0].name, "Admin");
+		assert_eq!(folders[0].folders[0].routes["ban"].method.as_deref(), Some("DELETE"));
+	}
+
+	#[test]
+	fn merges_collection_and_environment_variables() {
+		let environment = r#"{ "values": [
+			{ "key": "base", "value": "https://staging.example.com" },
+			{ "key": "off", "value": "x", "enabled": false },
+			{ "key": "page", "value": 2 }
+		] }"#;
+		let (root, _) = collection(FIXTURE, Some(environment)).expect("valid collection");
+		assert_eq!(root.env.get("base").and_then(|x| x.as_str()), Some("https://staging.example.com"));
+		assert_eq!(root.env.get("token").and_then(|x| x.as_str()), Some("${secret}"));
+		assert_eq!(root.env.get("page").and_then(|x| x.as_integer()), Some(2));
+		assert!(!root.env.contains_key("off"));
+	}
+
+	#[test]
+	fn raw_body_gets_content_type_from_language() {
+		let (_, folders) = collection(FIXTURE, None).expect("valid collection");
+		let create = &folders[0].routes["create-user"];
+		assert_eq!(create.headers, Some(vec!["Content-Type: application/json".to_string()]));
+		match &create.body {
+			Some(StringOr::T(BodyConfig::Json(table))) => assert_eq!(table.get("name").and_then(|x| x.as_str()), Some("${name}")),
+			other => panic!("expected json body, got {other:?}"),
+		}
+
+		let text = request(r#"{ "mode": "raw", "raw": "hello" }"#, "[]");
+		assert_eq!(text.headers, Some(vec!["Content-Type: text/plain".to_string()]));
+		assert!(matches!(text.body, Some(StringOr::Str(ref x)) if x == "hello"));
+
+		// explicit header wins over language
+		let xml = request(
+			r#"{ "mode": "raw", "raw": "<a/>", "options": { "raw": { "language": "xml" } } }"#,
+			r#"[ { "key": "content-type", "value": "application/soap+xml" } ]"#,
+		);
+		assert_eq!(xml.headers, Some(vec!["content-type: application/soap+xml".to_string()]));
+	}
+
+	#[test]
+	fn encodes_urlencoded_fields() {
+		let endpoint = request(r#"{ "mode": "urlencoded", "urlencoded": [
+			{ "key": "q", "value": "a b" },
+			{ "key": "user", "value": "{{user}}" },
+			{ "key": "skip", "value": "x", "disabled": true }
+		] }"#, "[]");
+		assert_eq!(endpoint.headers, Some(vec!["Content-Type: application/x-www-form-urlencoded".to_string()]));
+		assert!(matches!(endpoint.body, Some(StringOr::Str(ref x)) if x == "q=a%20b&user=${user}"));
+	}
+
+	#[test]
+	fn wraps_graphql_query_and_variables() {
+		let endpoint = request(r#"{ "mode": "graphql", "graphql": { "query": "{ me { id } }", "variables": "{\"id\": 1}" } }"#, "[]");
+		assert_eq!(endpoint.headers, Some(vec!["Content-Type: application/json".to_string()]));
+		match endpoint.body {
+			Some(StringOr::T(BodyConfig::Json(table))) => {
+				assert_eq!(table.get("query").and_then(|x| x.as_str()), Some("{ me { id } }"));
+				assert_eq!(table.get("variables").and_then(|x| x.get("id")).and_then(|x| x.as_integer()), Some(1));
+			},
+			other => panic!("expected json body, got {other:?}"),
+		}
+	}
+}
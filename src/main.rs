@@ -1,14 +1,18 @@
 mod model;
+mod bench;
 mod errors;
 mod ext;
 mod fmt;
+mod import;
+mod plan;
+mod export;
 
 use std::str::FromStr;
 
 use clap::{Parser, Subcommand};
 
 use ext::FillableFromEnvironment;
-use fmt::{PrintableResult, ReportableResult};
+use fmt::{OutputFormat, PrintableResult, ReportableResult, RunReport, RunResult};
 use indexmap::IndexMap;
 pub use model::PostWomanCollection;
 pub use errors::PostWomanError;
@@ -31,9 +35,17 @@ struct PostWomanArgs {
 	#[arg(short = 'M', long, default_value_t = false)]
 	multi_thread: bool,
 
-	/// emit json report document instead of pretty printing
+	/// emit json report document instead of pretty printing, same as `--format json`
 	#[arg(short = 'R', long, default_value_t = false)]
 	report: bool,
+
+	/// output format for results
+	#[arg(short, long, value_enum, default_value_t = OutputFormat::Pretty)]
+	format: OutputFormat,
+
+	/// env profile to layer over base env, from `[env.profiles.<name>]` tables
+	#[arg(long)]
+	env: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -47,6 +59,14 @@ pub enum PostWomanActions {
 		#[arg(short, long, default_value_t = false)]
 		parallel: bool,
 
+		/// max routes in flight at the same time during parallel runs, unlimited by default
+		#[arg(long)]
+		concurrency: Option<usize>,
+
+		/// max requests per second sent to each host, for collections which don't set their own `rate_limit`
+		#[arg(long, value_parser = parse_rate)]
+		rate_limit: Option<f64>,
+
 		/// force debug extractor on all routes
 		#[arg(long, default_value_t = false)]
 		debug: bool,
@@ -54,6 +74,36 @@ pub enum PostWomanActions {
 		/// print matched routes but don't perform requests
 		#[arg(long, default_value_t = false)]
 		dry_run: bool,
+
+		/// stop running routes after first failure, remaining ones are skipped
+		#[arg(long, default_value_t = false)]
+		fail_fast: bool,
+
+		/// record all requests and responses into a HAR archive at given path
+		#[arg(long)]
+		har: Option<std::path::PathBuf>,
+	},
+
+	/// repeatedly send matching routes and measure throughput and latency
+	Bench {
+		/// regex query filter, routes are benchmarked one after the other
+		query: String,
+
+		/// total requests to send for each route, defaults to 100 unless a duration is given
+		#[arg(short = 'n', long)]
+		requests: Option<usize>,
+
+		/// keep sending requests for this many seconds
+		#[arg(short, long)]
+		duration: Option<u64>,
+
+		/// how many requests to keep in flight at the same time
+		#[arg(long, default_value_t = 1)]
+		concurrency: usize,
+
+		/// limit requests per second, across all concurrent workers
+		#[arg(long, value_parser = parse_rate)]
+		rate: Option<f64>,
 	},
 
 	/// show all registered routes in current collection
@@ -62,141 +112,616 @@ pub enum PostWomanActions {
 		#[arg(short, long, default_value_t = false)]
 		compact: bool,
 	},
+
+	/// import routes from other tools, appending them to current collection
+	Import {
+		#[clap(subcommand)]
+		source: ImportSource,
+
+		/// print generated routes instead of appending them to collection
+		#[arg(long, default_value_t = false, global = true)]
+		print: bool,
+	},
+
+	/// export matching routes in formats understood by other tools
+	Export {
+		#[clap(subcommand)]
+		target: ExportTarget,
+	},
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportTarget {
+	/// print a curl command line for each matching route
+	Curl {
+		/// regex query filter, export all with '.'
+		query: String,
+	},
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ImportSource {
+	/// import a curl command line, read from stdin if not given
+	Curl {
+		/// name for the new route, derived from method and path if not given
+		#[arg(short, long)]
+		name: Option<String>,
+
+		/// curl command, either as a single quoted string or as separate arguments
+		#[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+		command: Vec<String>,
+	},
+
+	/// import all operations from an OpenAPI 3 spec, either json or yaml
+	Openapi {
+		/// path to spec document
+		spec: std::path::PathBuf,
+	},
+
+	/// import a Postman v2.1 collection export, folders become included collections
+	Postman {
+		/// path to exported collection
+		collection: std::path::PathBuf,
+
+		/// exported environment, its variables are added to collection env
+		#[arg(short, long)]
+		environment: Option<std::path::PathBuf>,
+	},
+
+	/// import requests recorded in a HAR archive, like the ones saved by browser devtools
+	Har {
+		/// path to HAR archive
+		archive: std::path::PathBuf,
+	},
+
+	/// import an Insomnia v4 export, folders become included collections
+	Insomnia {
+		/// path to exported json
+		export: std::path::PathBuf,
+	},
 }
 
 const DEFAULT_ACTION: PostWomanActions = PostWomanActions::List { compact: true };
 
-fn main() {
+fn main() -> std::process::ExitCode {
 	let args = PostWomanArgs::parse();
 	let multi_thread = args.multi_thread;
+	let format = if args.report { OutputFormat::Json } else { args.format };
 
 	// if we got a regex, test it early to avoid wasting work when invalid
-	if let Some(PostWomanActions::Run { ref query, .. }) = args.action {
+	if let Some(
+		PostWomanActions::Run { ref query, .. }
+		| PostWomanActions::Bench { ref query, .. }
+		| PostWomanActions::Export { target: ExportTarget::Curl { ref query } }
+	) = args.action {
 		// note that if you remove this test, there's another .expect() below you need to manage too!
 		if let Err(e) = regex::Regex::new(query) {
-			return eprintln!("! invalid regex filter: {e}");
+			eprintln!("! invalid regex filter: {e}");
+			return std::process::ExitCode::FAILURE;
 		}
 	}
 
+	if let Some(PostWomanActions::Import { ref source, print }) = args.action {
+		return match import_routes(&args.collection, source, print) {
+			Ok(()) => std::process::ExitCode::SUCCESS,
+			Err(e) => {
+				eprintln!("! {e}");
+				std::process::ExitCode::FAILURE
+			},
+		};
+	}
+
 	let mut collections = IndexMap::new();
 
-	if !load_collections(&mut collections, args.collection.clone(), &toml::Table::default()) {
-		return;
+	let mut found = false;
+	if !load_collections(&mut collections, args.collection.clone(), &model::EnvLayers::default(), args.env.as_deref(), &mut found) {
+		return std::process::ExitCode::FAILURE;
+	}
+
+	if let (Some(profile), false) = (&args.env, found) {
+		eprintln!("! env profile '{profile}' not found in any collection");
+		return std::process::ExitCode::FAILURE;
 	}
 
 	match args.action.as_ref().unwrap_or(&DEFAULT_ACTION) {
 		PostWomanActions::List { compact } => {
-			if args.report {
+			if format == OutputFormat::Json {
 				(collections, *compact).report();
 			} else {
 				(collections, *compact).print();
 			}
+			std::process::ExitCode::SUCCESS
+		},
+
+		PostWomanActions::Import { .. } => unreachable!("import is handled before loading collections"),
+
+		PostWomanActions::Export { target: ExportTarget::Curl { query } } => {
+			// this is always safe to compile because we tested it beforehand
+			let pattern = regex::Regex::new(query).expect("tested it before and still failed here???");
+			let mut res = std::process::ExitCode::SUCCESS;
+			for (namespace, collection) in collections {
+				let client = collection.client.fill(&collection.env);
+				for (name, endpoint) in collection.route {
+					let full_name = ext::full_name(&namespace, &name);
+					if pattern.find(&full_name).is_none() { continue };
+					match export::curl::command(&endpoint.fill(&collection.env), &client) {
+						Ok(cmd) => println!("# {full_name}\n{cmd}\n"),
+						Err(e) => {
+							eprintln!("! could not export {full_name}: {e}");
+							res = std::process::ExitCode::FAILURE;
+						},
+					}
+				}
+			}
+			res
 		},
 
-		PostWomanActions::Run { query, parallel, debug, dry_run } => {
+		PostWomanActions::Run { query, parallel, concurrency, rate_limit, debug, dry_run, fail_fast, har } => {
+			let opts = RunOptions {
+				parallel: *parallel,
+				rate_limit: *rate_limit,
+				debug: *debug,
+				dry_run: *dry_run,
+				fail_fast: *fail_fast,
+				record: har.is_some(),
+			};
+			let har = har.clone();
+
+			// this is always safe to compile because we tested it beforehand
+			let pattern = regex::Regex::new(query).expect("tested it before and still failed here???");
+			let plan = match plan::order(&collections, &pattern) {
+				Ok(x) => x,
+				Err(e) => {
+					eprintln!("! {e}");
+					return std::process::ExitCode::FAILURE;
+				},
+			};
+
 			let task = async move {
-				let mut pool = tokio::task::JoinSet::new();
-
-				for (collection_name, collection) in collections {
-					run_collection_endpoints(
-						collection_name,
-						collection,
-						query.clone(),
-						*parallel,
-						*debug,
-						*dry_run,
-						args.report,
+				let mut pool = RunPool {
+					permits: concurrency.map(|n| std::sync::Arc::new(tokio::sync::Semaphore::new(n.max(1)))),
+					..Default::default()
+				};
+				let captures = Captures::default();
+				let mut jars = CookieJars::default();
+				let mut report = RunReport::new(format, har);
+				let mut runs = IndexMap::new();
+				let mut signals = Signals::default();
+
+				for (namespace, name, deps) in plan {
+					let collection = &collections[&namespace];
+					// collections are set up only once one of their routes actually runs
+					if !runs.contains_key(&namespace) {
+						let mut run = CollectionRun::new(&namespace, collection, &opts, &mut jars, &pool);
+						run.ready = run_fixtures(
+							&namespace,
+							"setup",
+							&collection.setup,
+							false,
+							&run,
+							&opts,
+							captures.clone(),
+							&mut signals,
+							&mut report,
+							&mut pool
+						).await;
+						runs.insert(namespace.clone(), run);
+					}
+					let run = &runs[&namespace];
+					if !run.ready {
+						skip_route(namespace, name, ", setup failed", &mut signals, &mut report);
+						continue;
+					}
+					let endpoint = collection.route[&name].clone();
+					run_route(
+						namespace,
+						name,
+						endpoint,
+						deps,
+						run,
+						&opts,
+						captures.clone(),
+						&mut signals,
+						&mut report,
+						&mut pool
+					).await;
+				}
+
+				while let Some(j) = pool.tasks.join_next_with_id().await {
+					match j {
+						Ok((_id, res)) => {
+							if opts.fail_fast && !res.success() {
+								pool.tasks.abort_all();
+							}
+							report.push(res);
+						},
+						Err(e) => {
+							let (namespace, name) = pool.names.remove(&e.id()).unwrap_or_default();
+							if e.is_cancelled() {
+								report.push(RunResult::skipped(namespace, name));
+							} else {
+								eprintln!("! error joining task: {e}");
+								report.push(RunResult::failed(namespace, name, PostWomanError::Panic(e.to_string())));
+							}
+						},
+					}
+				}
+
+				// teardown goes in reverse, so later collections clean up before the ones they may rely on
+				for (namespace, run) in runs.iter().rev() {
+					run_fixtures(
+						namespace,
+						"teardown",
+						&collections[namespace].teardown,
+						true,
+						run,
+						&opts,
+						captures.clone(),
+						&mut signals,
+						&mut report,
 						&mut pool
 					).await;
 				}
 
-				while let Some(j) = pool.join_next().await {
-					if let Err(e) = j {
-						eprintln!("! error joining task: {e}");
+				for (path, jar) in jars.iter() {
+					let Some(path) = path else { continue };
+					match jar.save(std::path::Path::new(path)) {
+						Ok(n) => eprintln!(" + saved {n} cookies into {path}"),
+						Err(e) => eprintln!(" ! could not save cookies into {path}: {e}"),
 					}
 				}
+
+				report.finish()
 			};
 
 			eprintln!("~@ {APP_USER_AGENT}");
-			if multi_thread {
-				tokio::runtime::Builder::new_multi_thread()
-					.enable_all()
-					.build()
-					.expect("failed creating tokio multi-thread runtime")
-					.block_on(task)
-			} else {
-				tokio::runtime::Builder::new_current_thread()
-					.enable_all()
-					.build()
-					.expect("failed creating tokio current-thread runtime")
-					.block_on(task)
-			}
+			runtime(multi_thread).block_on(task)
+		},
+
+		PostWomanActions::Bench { query, requests, duration, concurrency, rate } => {
+			// this is always safe to compile because we tested it beforehand
+			let pattern = regex::Regex::new(query).expect("tested it before and still failed here???");
+			let opts = bench::BenchOptions {
+				requests: requests.or(if duration.is_none() { Some(100) } else { None }),
+				duration: duration.map(std::time::Duration::from_secs),
+				concurrency: *concurrency,
+				rate: *rate,
+			};
+
+			let task = async move {
+				let mut res = std::process::ExitCode::SUCCESS;
+				for (namespace, collection) in collections {
+					for result in bench::bench_collection(namespace, collection, &pattern, &opts).await {
+						if !result.success() { res = std::process::ExitCode::FAILURE };
+						if format == OutputFormat::Json {
+							result.report();
+						} else {
+							result.print();
+						}
+					}
+				}
+				res
+			};
+
+			eprintln!("~@ {APP_USER_AGENT}");
+			runtime(multi_thread).block_on(task)
 		},
 	}
 }
 
-// TODO too many arguments
-async fn run_collection_endpoints(
-	namespace: String,
-	collection: PostWomanCollection,
-	query: String,
+// rates end up as intervals between requests, reject the ones which wouldn't make one
+fn parse_rate(raw: &str) -> Result<f64, String> {
+	let rate = raw.parse::<f64>().map_err(|e| e.to_string())?;
+	model::rate_interval(rate).map_err(|e| e.to_string())?;
+	Ok(rate)
+}
+
+fn runtime(multi_thread: bool) -> tokio::runtime::Runtime {
+	if multi_thread {
+		tokio::runtime::Builder::new_multi_thread()
+			.enable_all()
+			.build()
+			.expect("failed creating tokio multi-thread runtime")
+	} else {
+		tokio::runtime::Builder::new_current_thread()
+			.enable_all()
+			.build()
+			.expect("failed creating tokio current-thread runtime")
+	}
+}
+
+/// options for a run, shared by all collections
+#[derive(Debug, Clone)]
+struct RunOptions {
 	parallel: bool,
+	/// default per-host requests per second
+	rate_limit: Option<f64>,
 	debug: bool,
 	dry_run: bool,
-	report: bool,
-	pool: &mut tokio::task::JoinSet<()>
+	fail_fast: bool,
+	/// keep full exchanges for HAR archive
+	record: bool,
+}
+
+/// tasks spawned for parallel runs, with route names to report them if they never complete
+#[derive(Default)]
+struct RunPool {
+	tasks: tokio::task::JoinSet<RunResult>,
+	names: std::collections::HashMap<tokio::task::Id, (String, String)>,
+	/// limits routes in flight across the whole run, if requested
+	permits: Option<std::sync::Arc<tokio::sync::Semaphore>>,
+	/// spaces out requests to rate limited hosts
+	throttle: std::sync::Arc<model::HostThrottle>,
+}
+
+/// variables captured by routes during a run, visible to all routes executed afterwards
+type Captures = std::sync::Arc<std::sync::RwLock<toml::Table>>;
+
+/// cookie jars used during a run, by file they're stored into. collections without a file share the same jar
+type CookieJars = IndexMap<Option<String>, std::sync::Arc<model::CookieJar>>;
+
+/// outcome of each route scheduled so far, None until it completes, true only if it passed
+type Signals = std::collections::HashMap<String, tokio::sync::watch::Receiver<Option<bool>>>;
+
+/// client options and http client built from them, shared by all routes using them
+type SharedClient = (std::sync::Arc<model::ClientConfig>, std::sync::Arc<Result<reqwest::Client, String>>);
+
+/// what all routes of a collection share during a run
+struct CollectionRun {
+	env: std::sync::Arc<toml::Table>,
+	client: std::sync::Arc<model::ClientConfig>,
+	http: std::sync::Arc<Result<reqwest::Client, String>>,
+	/// clients of routes overriding client options, by route name, as they can't share collection connections
+	overrides: std::collections::HashMap<String, SharedClient>,
+	tokens: std::sync::Arc<Option<model::TokenStore>>,
+	/// run and collection concurrency limits, both apply
+	permits: [Option<std::sync::Arc<tokio::sync::Semaphore>>; 2],
+	rate_limit: Option<f64>,
+	/// whether setup routes all passed, routes are skipped otherwise
+	ready: bool,
+}
+
+impl CollectionRun {
+	fn new(namespace: &str, collection: &PostWomanCollection, opts: &RunOptions, jars: &mut CookieJars, pool: &RunPool) -> Self {
+		let mut client = collection.client.clone().fill(&collection.env);
+		if client.keeps_cookies() {
+			let jar = jars.entry(client.cookie_jar.clone())
+				.or_insert_with_key(|path| match path {
+					None => Default::default(),
+					Some(path) => match model::CookieJar::load(std::path::Path::new(path)) {
+						Ok(jar) => std::sync::Arc::new(jar),
+						Err(e) => {
+							eprintln!(" ! could not load cookies from {path}, starting empty: {e}");
+							Default::default()
+						},
+					},
+				});
+			client.jar = Some(jar.clone());
+		}
+		// one client for the whole collection, so connections are pooled and reused across routes
+		let http = std::sync::Arc::new(client.client().map_err(|e| e.to_string()));
+		// built once too, so their retries reuse connections as well
+		let fixtures = collection.setup.iter().map(|(name, x)| (format!("setup.{name}"), x))
+			.chain(collection.teardown.iter().map(|(name, x)| (format!("teardown.{name}"), x)));
+		let overrides = collection.route.iter()
+			.map(|(name, x)| (name.clone(), x))
+			.chain(fixtures)
+			.filter_map(|(name, endpoint)| {
+				let route_client = client.for_route(&endpoint.clone().fill(&collection.env))?;
+				let http = route_client.client().map_err(|e| e.to_string());
+				Some((name, (std::sync::Arc::new(route_client), std::sync::Arc::new(http))))
+			})
+			.collect();
+		let tokens = std::sync::Arc::new(client.oauth2.clone().map(|x| model::TokenStore::new(x, namespace)));
+		// whichever limit is stricter ends up mattering
+		let permits = [
+			pool.permits.clone(),
+			client.max_concurrency.map(|n| std::sync::Arc::new(tokio::sync::Semaphore::new(n.max(1)))),
+		];
+		let rate_limit = client.rate_limit.or(opts.rate_limit);
+
+		CollectionRun {
+			env: std::sync::Arc::new(collection.env.clone()),
+			client: std::sync::Arc::new(client),
+			http,
+			overrides,
+			tokens,
+			permits,
+			rate_limit,
+			ready: true,
+		}
+	}
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_route(
+	namespace: String,
+	name: String,
+	mut endpoint: model::EndpointConfig,
+	deps: Vec<String>,
+	run: &CollectionRun,
+	opts: &RunOptions,
+	captures: Captures,
+	signals: &mut Signals,
+	report: &mut RunReport,
+	pool: &mut RunPool,
 ) {
-	// this is always safe to compile because we tested it beforehand
-	let pattern = regex::Regex::new(&query).expect("tested it before and still failed here???");
-	let env = std::sync::Arc::new(collection.env);
-	let client = std::sync::Arc::new(collection.client.fill(&env));
-
-	for (name, mut endpoint) in collection.route {
-		let full_name = ext::full_name(&namespace, &name);
-		if pattern.find(&full_name).is_none() { continue };
-
-		if debug { endpoint.extract = Some(ext::StringOr::T(model::ExtractorConfig::Debug)) };
-		let _client = client.clone();
-		let _env = env.clone();
-		let _namespace = namespace.clone();
-
-		let task = async move {
-			let before = chrono::Local::now();
-			eprintln!(" : [{}] {full_name} \tsending request...", before.format(fmt::TIMESTAMP_FMT));
-
-			let res = if dry_run {
-				Ok("".to_string())
-			} else {
-				endpoint
-					.fill(&_env)
-					.execute(&_client)
-					.await
-			};
+	if opts.fail_fast && report.failed > 0 {
+		return skip_route(namespace, name, "", signals, report);
+	}
 
-			let after = chrono::Local::now();
-			let elapsed = (after - before).num_milliseconds();
+	let full_name = ext::full_name(&namespace, &name);
+	let (done, signal) = tokio::sync::watch::channel(None);
+	// plan puts dependencies first, so they're always scheduled already
+	let deps: Vec<_> = deps.into_iter()
+		.filter_map(|dep| signals.get(&dep).map(|x| (dep, x.clone())))
+		.collect();
+	signals.insert(full_name.clone(), signal);
+
+	if opts.debug { endpoint.extract = Some(ext::StringOr::T(model::ExtractorConfig::Debug)) };
+	let (_client, _http) = match run.overrides.get(&name) {
+		Some((client, http)) => (client.clone(), http.clone()),
+		None => (run.client.clone(), run.http.clone()),
+	};
+	let _tokens = run.tokens.clone();
+	let _env = run.env.clone();
+	let _namespace = namespace.clone();
+	let _captures = captures.clone();
+	let _permits = run.permits.clone();
+	let _throttle = pool.throttle.clone();
+	let rate_limit = run.rate_limit;
+	let dry_run = opts.dry_run;
+	let record = opts.record;
+	let _name = name.clone();
+
+	let task = async move {
+		// a dependency which failed, was skipped or got cancelled means this route can't run
+		for (dep, mut signal) in deps {
+			let passed = signal.wait_for(Option::is_some).await.map(|x| x.unwrap_or(false)).unwrap_or(false);
+			if !passed {
+				eprintln!(" - [{}] {full_name} \tskipped, {dep} didn't pass", chrono::Local::now().format(fmt::TIMESTAMP_FMT));
+				return RunResult::skipped(_namespace, name);
+			}
+		}
 
-			let timestamp = after.format(fmt::TIMESTAMP_FMT);
-			let symbol = if res.is_ok() { " + " } else { "<!>" };
-			let verb = if res.is_ok() { "done in" } else { "failed after" };
-			eprintln!("{symbol}[{timestamp}] {_namespace}::{name} \t{verb} {elapsed}ms", );
+		// wait for a free slot before starting, so queued time isn't counted as route time
+		let mut _slots = Vec::new();
+		for permits in _permits.into_iter().flatten() {
+			_slots.push(permits.acquire_owned().await);
+		}
 
-			if report {
-				(res, _namespace, name, elapsed).report();
-			} else {
-				(res, _namespace, name, elapsed).print();
+		let before = chrono::Local::now();
+		eprintln!(" : [{}] {full_name} \tsending request...", before.format(fmt::TIMESTAMP_FMT));
+
+		let mut exchange = None;
+		let mut version = None;
+		let mut timings = None;
+		let mut attempts = 0;
+		let mut secrets = Vec::new();
+		let res = if dry_run {
+			Ok(model::EndpointOutput::default())
+		} else {
+			// captured variables are resolved when the route starts, not when it's scheduled.
+			// they come from this very run, so they win over process env too
+			let mut vars = model::EndpointConfig::default_vars(&_env);
+			vars.extend(_captures.read().expect("capture store poisoned").iter().map(|(k, v)| (k.clone(), ext::stringify_toml(v))));
+			let mut endpoint = endpoint.fill_vars(&vars);
+			let tokens = _tokens.as_ref().as_ref();
+			let client = &_client;
+			let http = (*_http).clone().map_err(PostWomanError::InvalidClient);
+			let retry = _client.retry.clone().unwrap_or_default().merge(&endpoint.retry.clone().unwrap_or_default());
+			let auth = endpoint.auth.clone();
+			let sent = match http {
+				Err(e) => Err(e),
+				Ok(http) => loop {
+					// oauth2 puts its token in route auth, start clean so it gets refreshed if needed
+					endpoint.auth = auth.clone();
+					attempts += 1;
+					if let Some(rate) = rate_limit {
+						_throttle.wait(&endpoint.url(client.base.as_deref()), rate).await;
+					}
+					let sent = endpoint.send_authenticated(client, &http, tokens).await;
+					let Some((reason, delay)) = retry.next(attempts, &sent) else { break sent };
+					eprintln!(
+						" ~ [{}] {full_name} \tattempt {attempts} failed ({reason}), retrying in {}ms",
+						chrono::Local::now().format(fmt::TIMESTAMP_FMT), delay.as_millis()
+					);
+					tokio::time::sleep(delay).await;
+				},
+			};
+			secrets = endpoint.auth.as_ref().map(|x| x.secrets()).unwrap_or_default();
+			secrets.extend(_client.secrets());
+			secrets.extend(endpoint.tls.iter().flat_map(|x| x.secrets()));
+			match sent {
+				Err(e) => Err(e),
+				Ok(x) => {
+					version = Some(x.response.version);
+					timings = Some(x.timings);
+					let res = endpoint.check(&x);
+					if record { exchange = Some(x) };
+					res
+				},
 			}
 		};
 
-		if parallel {
-			pool.spawn(task);
-		} else {
-			task.await;
+		// captures keep real values, but credentials shouldn't end up in outputs, errors included
+		let res = match res {
+			Ok(out) => {
+				_captures.write().expect("capture store poisoned").extend(out.captures);
+				Ok(model::redact(&out.result, &secrets))
+			},
+			Err(e) => Err(model::redact_error(e, &secrets)),
+		};
+
+		let after = chrono::Local::now();
+		let elapsed = (after - before).num_milliseconds();
+
+		let timestamp = after.format(fmt::TIMESTAMP_FMT);
+		let symbol = if res.is_ok() { " + " } else { "<!>" };
+		let verb = if res.is_ok() { "done in" } else { "failed after" };
+		match timings {
+			Some(ref t) => eprintln!("{symbol}[{timestamp}] {_namespace}::{name} \t{verb} {elapsed}ms \t({t})"),
+			None => eprintln!("{symbol}[{timestamp}] {_namespace}::{name} \t{verb} {elapsed}ms"),
+		}
+
+		// dependents only care whether this passed, nobody listening is fine too
+		let _ = done.send(Some(res.is_ok()));
+
+		RunResult { namespace: _namespace, name, result: Some(res), elapsed, attempts, version, timings, exchange }
+	};
+
+	if opts.parallel {
+		let handle = pool.tasks.spawn(task);
+		pool.names.insert(handle.id(), (namespace.clone(), _name));
+	} else {
+		report.push(task.await);
+	}
+}
+
+/// report a route as skipped without running it, routes depending on it will be skipped too
+fn skip_route(namespace: String, name: String, reason: &str, signals: &mut Signals, report: &mut RunReport) {
+	let full_name = ext::full_name(&namespace, &name);
+	// sender is dropped right away, so anyone waiting sees this never passed
+	signals.insert(full_name.clone(), tokio::sync::watch::channel(None).1);
+	eprintln!(" - [{}] {full_name} \tskipped{reason}", chrono::Local::now().format(fmt::TIMESTAMP_FMT));
+	report.push(RunResult::skipped(namespace, name));
+}
+
+/// run setup or teardown routes of a collection one at a time, regardless of filter and fail fast.
+/// returns whether all of them passed, stops at first failure unless asked to keep going
+#[allow(clippy::too_many_arguments)]
+async fn run_fixtures(
+	namespace: &str,
+	kind: &str,
+	routes: &IndexMap<String, model::EndpointConfig>,
+	keep_going: bool,
+	run: &CollectionRun,
+	opts: &RunOptions,
+	captures: Captures,
+	signals: &mut Signals,
+	report: &mut RunReport,
+	pool: &mut RunPool,
+) -> bool {
+	let opts = RunOptions { parallel: false, fail_fast: false, ..opts.clone() };
+	let mut passed = true;
+	for (name, endpoint) in routes {
+		let failed = report.failed;
+		run_route(namespace.to_string(), format!("{kind}.{name}"), endpoint.clone(), Vec::new(), run, &opts, captures.clone(), signals, report, pool).await;
+		if report.failed > failed {
+			passed = false;
+			if !keep_going { break };
 		}
 	}
+	passed
 }
 
-fn load_collections(store: &mut IndexMap<String, PostWomanCollection>, mut path: std::path::PathBuf, parent_env: &toml::Table) -> bool {
+fn load_collections(
+	store: &mut IndexMap<String, PostWomanCollection>,
+	mut path: std::path::PathBuf,
+	parent_env: &model::EnvLayers,
+	profile: Option<&str>,
+	found: &mut bool,
+) -> bool {
 	let collection_raw = match std::fs::read_to_string(&path) {
 		Ok(x) => x,
 		Err(e) => {
@@ -213,12 +738,30 @@ fn load_collections(store: &mut IndexMap<String, PostWomanCollection>, mut path:
 		},
 	};
 
-	collection.env.extend(parent_env.iter().map(|(k, v)| (k.clone(), v.clone())));
+	let parent_env = match collection.apply_profile(profile, parent_env) {
+		Ok((layers, defined)) => {
+			// collections without profiles at all just don't care
+			if let (Some(profile), Some(false)) = (profile, defined) {
+				eprintln!("! env profile '{profile}' not found in {path:?}, using its base env");
+			}
+			*found |= defined.unwrap_or(false);
+			layers
+		},
+		Err(e) => {
+			eprintln!("! error loading collection {path:?}: {e}");
+			return false;
+		},
+	};
 
 	let name = path.to_string_lossy().replace(".toml", "");
 	let mut to_include = Vec::new();
 
 	path.pop();
+	collection.client.relative_to(&path);
+	for endpoint in collection.route.values_mut().chain(collection.setup.values_mut()).chain(collection.teardown.values_mut()) {
+		endpoint.relative_to(&path);
+	}
+
 	for include in &collection.include {
 		let mut base = path.clone();
 		let new = std::path::PathBuf::from_str(include).expect("infallible");
@@ -226,14 +769,98 @@ fn load_collections(store: &mut IndexMap<String, PostWomanCollection>, mut path:
 		to_include.push(base);
 	}
 
-	let parent_env = collection.env.clone();
 	store.insert(name, collection);
 
 	for base in to_include {
-		if !load_collections(store, base, &parent_env) {
+		if !load_collections(store, base, &parent_env, profile, found) {
 			return false;
 		}
 	}
 
 	true
 }
+
+fn import_routes(path: &std::path::Path, source: &ImportSource, print: bool) -> Result<(), PostWomanError> {
+	// collection may not exist yet, we will create it
+	let collection: Option<PostWomanCollection> = match std::fs::read_to_string(path) {
+		Ok(raw) => Some(toml::from_str(&raw)?),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+		Err(e) => return Err(e.into()),
+	};
+	let existing_base = collection.as_ref().and_then(|x| x.client.base.clone());
+
+	let mut imported = PostWomanCollection::default();
+	let mut folders = Vec::new();
+
+	match source {
+		ImportSource::Curl { name, command } => {
+			let command = match command.as_slice() {
+				[] => std::io::read_to_string(std::io::stdin())?,
+				[single] => single.clone(),
+				_ => shell_words::join(command),
+			};
+			let words = shell_words::split(&command)
+				.map_err(|e| PostWomanError::ImportError(format!("invalid curl command: {e}")))?;
+			let endpoint = import::curl::endpoint(&words, existing_base.as_deref())?;
+			let name = name.clone().unwrap_or_else(||
+				import::route_name(endpoint.method.as_deref().unwrap_or("GET"), &endpoint.path)
+			);
+			imported.route.insert(name, endpoint);
+		},
+
+		ImportSource::Openapi { spec } => {
+			let spec = import::openapi::parse(&std::fs::read_to_string(spec)?)?;
+			let base = match collection {
+				Some(_) => existing_base.clone(),
+				None => import::openapi::server(&spec),
+			};
+			imported.route = import::openapi::routes(&spec, base.as_deref());
+			imported.client.base = base;
+		},
+
+		ImportSource::Har { archive } => {
+			let entries = import::har::parse(&std::fs::read_to_string(archive)?)?;
+			let base = match collection {
+				Some(_) => existing_base.clone(),
+				None => import::har::origin(&entries),
+			};
+			imported.route = import::har::routes(&entries, base.as_deref());
+			imported.client.base = base;
+		},
+
+		ImportSource::Postman { collection, environment } => {
+			let environment = environment.as_ref().map(std::fs::read_to_string).transpose()?;
+			(imported, folders) = import::postman::collection(&std::fs::read_to_string(collection)?, environment.as_deref())?;
+		},
+
+		ImportSource::Insomnia { export } => {
+			(imported, folders) = import::insomnia::collection(&std::fs::read_to_string(export)?)?;
+		},
+	}
+
+	// existing client options are kept, so routes relative to another base must become absolute
+	if let (Some(existing), Some(base)) = (&existing_base, &imported.client.base) {
+		if existing != base {
+			make_absolute(&mut imported.route, base);
+			for folder in folders.iter_mut() {
+				make_absolute_folder(folder, base);
+			}
+		}
+	}
+
+	import::save_tree(path, imported, folders, print)
+}
+
+fn make_absolute(routes: &mut indexmap::IndexMap<String, model::EndpointConfig>, base: &str) {
+	for endpoint in routes.values_mut().filter(|x| !x.absolute.unwrap_or(false)) {
+		endpoint.path = format!("{base}{}", endpoint.path);
+		endpoint.absolute = Some(true);
+	}
+}
+
+fn make_absolute_folder(folder: &mut import::ImportedFolder, base: &str) {
+	make_absolute(&mut folder.routes, base);
+	for sub in folder.folders.iter_mut() {
+		make_absolute_folder(sub, base);
+	}
+}
@@ -0,0 +1,212 @@
+use crate::ext::{stringify_json, FillableFromEnvironment};
+
+use super::BufferedResponse;
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AssertConfig {
+	/// expected status code: a single code, a list of allowed codes or a { min, max } range
+	pub status: Option<StatusAssertion>,
+	/// max response time, in milliseconds
+	pub time: Option<u64>,
+	/// bounds for response body size, in bytes
+	pub size: Option<Bounds<usize>>,
+	/// checks on response headers, just presence if neither `equals` nor `matches` is given
+	pub headers: Option<Vec<HeaderAssertion>>,
+	/// checks on JQ queries ran on json response body
+	pub json: Option<Vec<JsonAssertion>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum StatusAssertion {
+	Code(u16),
+	Set(Vec<u16>),
+	Range(Bounds<u16>),
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Bounds<T> {
+	pub min: Option<T>,
+	pub max: Option<T>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HeaderAssertion {
+	pub key: String,
+	pub equals: Option<String>,
+	pub matches: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct JsonAssertion {
+	pub query: String,
+	pub equals: Option<String>,
+	pub contains: Option<String>,
+	pub matches: Option<String>,
+}
+
+impl<T: PartialOrd + std::fmt::Display> Bounds<T> {
+	fn check(&self, value: T, what: &str) -> Option<String> {
+		if let Some(ref min) = self.min {
+			if value < *min {
+				return Some(format!("{what} {value} is lower than {min}"));
+			}
+		}
+		if let Some(ref max) = self.max {
+			if value > *max {
+				return Some(format!("{what} {value} is greater than {max}"));
+			}
+		}
+		None
+	}
+}
+
+impl StatusAssertion {
+	fn check(&self, status: u16) -> Option<String> {
+		match self {
+			StatusAssertion::Code(code) => if status != *code {
+				return Some(format!("status {status} is not {code}"));
+			},
+			StatusAssertion::Set(codes) => if !codes.contains(&status) {
+				return Some(format!("status {status} is not one of {codes:?}"));
+			},
+			StatusAssertion::Range(bounds) => return bounds.check(status, "status"),
+		}
+		None
+	}
+}
+
+impl AssertConfig {
+	/// run all checks against given response, returning a description for each failed one
+	pub fn check(&self, res: &BufferedResponse, elapsed: std::time::Duration) -> Vec<String> {
+		let mut failures = Vec::new();
+
+		if let Some(ref status) = self.status {
+			failures.extend(status.check(res.status.as_u16()));
+		}
+
+		if let Some(max) = self.time {
+			let elapsed = elapsed.as_millis();
+			if elapsed > max as u128 {
+				failures.push(format!("response took {elapsed}ms, more than {max}ms"));
+			}
+		}
+
+		if let Some(ref size) = self.size {
+			failures.extend(size.check(res.body.len(), "body size"));
+		}
+
+		for header in self.headers.as_deref().unwrap_or(&[]) {
+			let Some(value) = res.headers.get(&header.key) else {
+				failures.push(format!("header '{}' not found", header.key));
+				continue;
+			};
+			let value = String::from_utf8_lossy(value.as_bytes());
+			if let Some(ref expected) = header.equals {
+				if *expected != value {
+					failures.push(format!("header '{}' is '{value}', expected '{expected}'", header.key));
+				}
+			}
+			if let Some(ref pattern) = header.matches {
+				failures.extend(check_regex(pattern, &value, &format!("header '{}'", header.key)));
+			}
+		}
+
+		if let Some(ref checks) = self.json {
+			match res.json() {
+				Err(e) => failures.push(format!("body is not valid json: {e}")),
+				Ok(json) => for check in checks {
+					failures.extend(check.check(&json));
+				},
+			}
+		}
+
+		failures
+	}
+}
+
+impl JsonAssertion {
+	fn check(&self, json: &serde_json::Value) -> Vec<String> {
+		let mut failures = Vec::new();
+		let what = format!("query '{}'", self.query);
+
+		let selection = match super::extractor::jq(&self.query, json.clone()) {
+			Ok(x) => x,
+			Err(e) => return vec![format!("{what} failed: {e}")],
+		};
+		let (result, elements) = match selection.as_slice() {
+			[serde_json::Value::Array(elements)] => (stringify_json(&selection[0]), elements.clone()),
+			[single] => (stringify_json(single), Vec::new()),
+			_ => (serde_json::to_string(&selection).unwrap_or_default(), selection.clone()),
+		};
+
+		if let Some(ref expected) = self.equals {
+			if *expected != result {
+				failures.push(format!("{what} got '{result}', expected '{expected}'"));
+			}
+		}
+
+		if let Some(ref needle) = self.contains {
+			// arrays must contain given element, anything else must contain given substring
+			let found = if elements.is_empty() {
+				result.contains(needle.as_str())
+			} else {
+				elements.iter().any(|x| stringify_json(x) == *needle)
+			};
+			if !found {
+				failures.push(format!("{what} got '{result}', which doesn't contain '{needle}'"));
+			}
+		}
+
+		if let Some(ref pattern) = self.matches {
+			failures.extend(check_regex(pattern, &result, &what));
+		}
+
+		failures
+	}
+}
+
+fn check_regex(pattern: &str, value: &str, what: &str) -> Option<String> {
+	match regex::Regex::new(pattern) {
+		Err(e) => Some(format!("invalid regex for {what}: {e}")),
+		Ok(re) if !re.is_match(value) => Some(format!("{what} got '{value}', which doesn't match '{pattern}'")),
+		Ok(_) => None,
+	}
+}
+
+impl FillableFromEnvironment for AssertConfig {
+	fn fill_vars(mut self, vars: &crate::ext::Vars) -> Self {
+
+		for (k, v) in vars {
+			let k_var = format!("${{{k}}}");
+			let replace = |x: Option<String>| x.map(|x| x.replace(&k_var, v));
+
+			if let Some(headers) = self.headers {
+				self.headers = Some(
+					headers.into_iter()
+						.map(|x| HeaderAssertion {
+							key: x.key.replace(&k_var, v),
+							equals: replace(x.equals),
+							matches: replace(x.matches),
+						})
+						.collect()
+				);
+			}
+
+			if let Some(json) = self.json {
+				self.json = Some(
+					json.into_iter()
+						.map(|x| JsonAssertion {
+							query: x.query.replace(&k_var, v),
+							equals: replace(x.equals),
+							contains: replace(x.contains),
+							matches: replace(x.matches),
+						})
+						.collect()
+				);
+			}
+		}
+
+		self
+	}
+}
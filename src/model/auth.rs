@@ -0,0 +1,244 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::ext::FillableFromEnvironment;
+use crate::PostWomanError;
+
+/// shown in place of credentials in listings and reports
+pub const REDACTED: &str = "********";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AuthConfig {
+	/// don't authenticate, useful to override client auth on some routes
+	None,
+	/// username and password, sent on every request
+	Basic { username: String, password: Option<String> },
+	/// token sent as `Authorization: Bearer <token>`
+	Bearer { token: String },
+	/// custom header, or query parameter if `in = "query"`
+	ApiKey {
+		key: String,
+		value: String,
+		#[serde(rename = "in")]
+		location: Option<ApiKeyLocation>,
+	},
+	/// HTTP digest, credentials are sent only after server challenge
+	Digest { username: String, password: String },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyLocation {
+	#[default]
+	Header,
+	Query,
+}
+
+/// where static credentials go on a request
+pub enum Credential {
+	Header(String, String),
+	Query(String, String),
+}
+
+impl AuthConfig {
+	/// credentials to put on request before sending it, digest needs a challenge first
+	pub fn credentials(&self) -> Option<Credential> {
+		match self {
+			AuthConfig::None | AuthConfig::Digest { .. } => None,
+			AuthConfig::Basic { username, password } =>
+				Some(Credential::Header("Authorization".to_string(), format!("Basic {}", basic(username, password.as_deref())))),
+			AuthConfig::Bearer { token } => Some(Credential::Header("Authorization".to_string(), format!("Bearer {token}"))),
+			AuthConfig::ApiKey { key, value, location } => match location.unwrap_or_default() {
+				ApiKeyLocation::Header => Some(Credential::Header(key.clone(), value.clone())),
+				ApiKeyLocation::Query => Some(Credential::Query(key.clone(), value.clone())),
+			},
+		}
+	}
+
+	/// answer a `WWW-Authenticate: Digest ...` challenge, returning Authorization header value
+	pub fn digest(&self, challenge: &str, method: &str, uri: &str, body: &[u8]) -> Result<String, PostWomanError> {
+		let AuthConfig::Digest { username, password } = self else {
+			return Err(PostWomanError::AuthError("not configured for digest auth".to_string()));
+		};
+		let Some(challenge) = challenge.trim().strip_prefix("Digest ") else {
+			return Err(PostWomanError::AuthError(format!("server didn't ask for digest auth: {challenge}")));
+		};
+		let params = parse_challenge(challenge);
+		let param = |k: &str| params.iter().find(|(x, _)| x.eq_ignore_ascii_case(k)).map(|(_, v)| v.as_str());
+
+		let realm = param("realm").unwrap_or_default();
+		let nonce = param("nonce").ok_or_else(|| PostWomanError::AuthError("digest challenge without nonce".to_string()))?;
+		let algorithm = param("algorithm").unwrap_or("MD5");
+		let hash: fn(&[u8]) -> String = match algorithm.to_uppercase().trim_end_matches("-SESS") {
+			"MD5" => |x| hex(&Md5::digest(x)),
+			"SHA-256" => |x| hex(&Sha256::digest(x)),
+			other => return Err(PostWomanError::AuthError(format!("unsupported digest algorithm {other}"))),
+		};
+		// prefer plain auth when both are offered, auth-int needs the whole body hashed
+		let qop = param("qop").map(|q| {
+			let options: Vec<&str> = q.split(',').map(|x| x.trim()).collect();
+			if options.contains(&"auth") || !options.contains(&"auth-int") { "auth" } else { "auth-int" }
+		});
+
+		let cnonce = format!(
+			"{:016x}",
+			std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos()
+		);
+		let nc = "00000001";
+
+		let mut ha1 = hash(format!("{username}:{realm}:{password}").as_bytes());
+		if algorithm.to_uppercase().ends_with("-SESS") {
+			ha1 = hash(format!("{ha1}:{nonce}:{cnonce}").as_bytes());
+		}
+		let ha2 = match qop {
+			// body is hashed as it is sent, it may well not be text
+			Some("auth-int") => hash(format!("{method}:{uri}:{}", hash(body)).as_bytes()),
+			_ => hash(format!("{method}:{uri}").as_bytes()),
+		};
+		let response = match qop {
+			Some(qop) => hash(format!("{ha1}:{nonce}:{nc}:{cnonce}:{qop}:{ha2}").as_bytes()),
+			None => hash(format!("{ha1}:{nonce}:{ha2}").as_bytes()),
+		};
+
+		let mut header = format!(
+			r#"Digest username="{username}", realm="{realm}", nonce="{nonce}", uri="{uri}", algorithm={algorithm}, response="{response}""#
+		);
+		if let Some(qop) = qop {
+			header.push_str(&format!(r#", qop={qop}, nc={nc}, cnonce="{cnonce}""#));
+		}
+		if let Some(opaque) = param("opaque") {
+			header.push_str(&format!(r#", opaque="{opaque}""#));
+		}
+		Ok(header)
+	}
+
+	/// values which should never show up in outputs
+	pub fn secrets(&self) -> Vec<String> {
+		let secrets = match self {
+			AuthConfig::None => vec![],
+			AuthConfig::Basic { username, password } => vec![
+				password.clone().unwrap_or_default(),
+				basic(username, password.as_deref()),
+			],
+			AuthConfig::Bearer { token } => vec![token.clone()],
+			AuthConfig::ApiKey { value, .. } => vec![value.clone()],
+			AuthConfig::Digest { password, .. } => vec![password.clone()],
+		};
+		secrets.into_iter().filter(|x| !x.is_empty()).collect()
+	}
+
+	/// same config, with credentials replaced, safe to display
+	pub fn redacted(&self) -> Self {
+		match self {
+			AuthConfig::None => AuthConfig::None,
+			AuthConfig::Basic { username, password } => AuthConfig::Basic {
+				username: username.clone(),
+				password: password.as_ref().map(|_| REDACTED.to_string()),
+			},
+			AuthConfig::Bearer { .. } => AuthConfig::Bearer { token: REDACTED.to_string() },
+			AuthConfig::ApiKey { key, location, .. } => AuthConfig::ApiKey {
+				key: key.clone(),
+				value: REDACTED.to_string(),
+				location: *location,
+			},
+			AuthConfig::Digest { username, .. } => AuthConfig::Digest {
+				username: username.clone(),
+				password: REDACTED.to_string(),
+			},
+		}
+	}
+}
+
+impl std::fmt::Display for AuthConfig {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.redacted() {
+			AuthConfig::None => write!(f, "none"),
+			AuthConfig::Basic { username, password } => write!(f, "basic {username}:{}", password.unwrap_or_default()),
+			AuthConfig::Bearer { token } => write!(f, "bearer {token}"),
+			AuthConfig::ApiKey { key, value, location } => match location.unwrap_or_default() {
+				ApiKeyLocation::Header => write!(f, "apikey header {key}: {value}"),
+				ApiKeyLocation::Query => write!(f, "apikey query {key}={value}"),
+			},
+			AuthConfig::Digest { username, password } => write!(f, "digest {username}:{password}"),
+		}
+	}
+}
+
+impl FillableFromEnvironment for AuthConfig {
+	fn fill_vars(mut self, vars: &crate::ext::Vars) -> Self {
+
+		for (k, v) in vars {
+			let k_var = format!("${{{k}}}");
+			let fields = match self {
+				AuthConfig::None => vec![],
+				AuthConfig::Basic { ref mut username, ref mut password } => {
+					let mut fields = vec![username];
+					fields.extend(password.as_mut());
+					fields
+				},
+				AuthConfig::Bearer { ref mut token } => vec![token],
+				AuthConfig::ApiKey { ref mut key, ref mut value, .. } => vec![key, value],
+				AuthConfig::Digest { ref mut username, ref mut password } => vec![username, password],
+			};
+			for field in fields {
+				*field = field.replace(&k_var, v);
+			}
+		}
+
+		self
+	}
+}
+
+/// same error, with given secrets replaced in its message if it contains any
+pub fn redact_error(error: PostWomanError, secrets: &[String]) -> PostWomanError {
+	let message = error.to_string();
+	let redacted = redact(&message, secrets);
+	if redacted == message { error } else { PostWomanError::Redacted(redacted) }
+}
+
+/// replace all given secrets in text
+pub fn redact(text: &str, secrets: &[String]) -> String {
+	let mut out = text.to_string();
+	for secret in secrets {
+		out = out.replace(secret, REDACTED);
+		// query credentials show up encoded in urls
+		let encoded = crate::ext::urlencode(secret);
+		if encoded != *secret {
+			out = out.replace(&encoded, REDACTED);
+		}
+	}
+	out
+}
+
+fn basic(username: &str, password: Option<&str>) -> String {
+	BASE64_STANDARD.encode(format!("{username}:{}", password.unwrap_or_default()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// key=value pairs, values may be quoted and contain commas
+fn parse_challenge(challenge: &str) -> Vec<(String, String)> {
+	let mut out = Vec::new();
+	let mut rest = challenge.trim();
+	while let Some((key, after)) = rest.split_once('=') {
+		let key = key.trim().trim_start_matches(',').trim().to_string();
+		let after = after.trim_start();
+		let (value, next) = match after.strip_prefix('"') {
+			Some(quoted) => match quoted.find('"') {
+				Some(end) => (quoted[..end].to_string(), &quoted[end + 1..]),
+				None => (quoted.to_string(), ""),
+			},
+			None => match after.find(',') {
+				Some(end) => (after[..end].trim().to_string(), &after[end..]),
+				None => (after.trim().to_string(), ""),
+			},
+		};
+		out.push((key, value));
+		rest = next.trim_start().trim_start_matches(',');
+	}
+	out
+}
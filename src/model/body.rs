@@ -0,0 +1,300 @@
+use crate::ext::{stringify_toml, urlencode, FillableFromEnvironment};
+use crate::PostWomanError;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum BodyConfig {
+	/// structured bodies, recognized by their `type` field
+	Typed(TypedBody),
+	/// any other table is sent as json
+	Json(toml::Table),
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileBody {
+	/// path to body file, relative to collection
+	pub file: String,
+	/// send file bytes as they are, without replacing variables, defaults to false
+	pub raw: Option<bool>,
+	/// Content-Type to send, guessed from file extension if not given
+	pub content_type: Option<String>,
+	/// variables to replace in file contents, collected when filled
+	#[serde(skip)]
+	vars: Vec<(String, String)>,
+	/// collection directory, if `file` comes from variables and can only be resolved once filled
+	#[serde(skip)]
+	dir: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TypedBody {
+	/// url encoded form, Content-Type defaults to application/x-www-form-urlencoded
+	Form { fields: toml::Table },
+	/// multipart form, Content-Type defaults to multipart/form-data with a generated boundary
+	Multipart { parts: Vec<PartConfig> },
+	/// explicit json body, in case it needs a `type` field itself
+	Json { content: toml::Table },
+	/// body read from a file, relative to collection
+	File(FileBody),
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PartConfig {
+	/// form field name
+	pub name: String,
+	/// inline part content
+	pub value: Option<String>,
+	/// upload this file as part content, relative to collection
+	pub file: Option<String>,
+	/// file name sent to server, defaults to uploaded file name
+	pub filename: Option<String>,
+	/// part Content-Type, guessed from file extension if not given
+	pub content_type: Option<String>,
+	/// collection directory, if `file` comes from variables and can only be resolved once filled
+	#[serde(skip)]
+	dir: Option<std::path::PathBuf>,
+}
+
+impl FileBody {
+	pub fn new(file: String, raw: bool) -> Self {
+		FileBody { file, raw: if raw { Some(true) } else { None }, content_type: None, vars: Vec::new(), dir: None }
+	}
+}
+
+impl BodyConfig {
+	/// encoded body, with the Content-Type it should be sent with if it needs one
+	pub fn payload(&self) -> Result<(Option<String>, Vec<u8>), PostWomanError> {
+		match self {
+			BodyConfig::Json(json) | BodyConfig::Typed(TypedBody::Json { content: json }) =>
+				Ok((None, serde_json::to_vec(json)?)),
+			BodyConfig::Typed(TypedBody::Form { fields }) =>
+				Ok((Some("application/x-www-form-urlencoded".to_string()), form(fields).into_bytes())),
+			BodyConfig::Typed(TypedBody::File(body)) => {
+				let path = std::path::Path::new(&body.file);
+				let mut content = std::fs::read(path)
+					.map_err(|e| PostWomanError::InvalidBody(format!("could not read body file {:?}: {e}", body.file)))?;
+				if !body.raw.unwrap_or(false) {
+					let mut text = String::from_utf8(content)
+						.map_err(|_| PostWomanError::InvalidBody(format!("body file {:?} is not valid utf8, set `raw = true` to send it as is", body.file)))?;
+					for (k, v) in &body.vars {
+						text = text.replace(k, v);
+					}
+					content = text.into_bytes();
+				}
+				let content_type = body.content_type.clone().unwrap_or_else(|| guess_mime(path).to_string());
+				Ok((Some(content_type), content))
+			},
+			BodyConfig::Typed(TypedBody::Multipart { parts }) => {
+				let boundary = format!(
+					"postwoman-{:x}",
+					std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos()
+				);
+				Ok((Some(format!("multipart/form-data; boundary={boundary}")), multipart(parts, &boundary)?))
+			},
+		}
+	}
+
+	/// human readable body, multipart files are not read
+	pub fn text(&self) -> Result<String, serde_json::Error> {
+		match self {
+			BodyConfig::Json(json) | BodyConfig::Typed(TypedBody::Json { content: json }) => serde_json::to_string(json),
+			BodyConfig::Typed(TypedBody::Form { fields }) => Ok(form(fields)),
+			BodyConfig::Typed(TypedBody::File(body)) => Ok(format!("@{}", body.file)),
+			BodyConfig::Typed(TypedBody::Multipart { parts }) => Ok(
+				parts.iter()
+					.map(|p| match (&p.value, &p.file) {
+						(_, Some(file)) => format!("{}=@{file}", p.name),
+						(value, None) => format!("{}={}", p.name, value.as_deref().unwrap_or_default()),
+					})
+					.collect::<Vec<_>>()
+					.join("\n")
+			),
+		}
+	}
+
+	/// make referenced file paths relative to given directory, unless already absolute
+	pub fn relative_to(&mut self, dir: &std::path::Path) {
+		match self {
+			BodyConfig::Typed(TypedBody::File(body)) => {
+				if body.file.starts_with("${") {
+					body.dir = Some(dir.to_path_buf());
+				}
+				body.file = relative(dir, &body.file);
+			},
+			BodyConfig::Typed(TypedBody::Multipart { parts }) => {
+				for part in parts {
+					if let Some(ref file) = part.file {
+						if file.starts_with("${") {
+							part.dir = Some(dir.to_path_buf());
+						}
+						part.file = Some(relative(dir, file));
+					}
+				}
+			},
+			_ => {},
+		}
+	}
+}
+
+impl FillableFromEnvironment for BodyConfig {
+	fn fill_vars(self, vars: &crate::ext::Vars) -> Self {
+		let replace = |table: toml::Table| {
+			let mut table = toml::Value::Table(table);
+			for (k, v) in vars {
+				table = super::endpoint::replace_recursive(table, &format!("${{{k}}}"), v);
+			}
+			let toml::Value::Table(out) = table
+			else { unreachable!("we put in a table, we get out a table") };
+			out
+		};
+
+		match self {
+			BodyConfig::Json(json) => BodyConfig::Json(replace(json)),
+			// file contents are only read when sending, keep variables around until then
+			BodyConfig::Typed(TypedBody::File(mut body)) => {
+				body.vars = vars.iter().map(|(k, v)| (format!("${{{k}}}"), v.clone())).collect();
+				for (k, v) in &body.vars {
+					body.file = body.file.replace(k, v);
+				}
+				if let Some(dir) = body.dir.take() {
+					body.file = relative(&dir, &body.file);
+				}
+				BodyConfig::Typed(TypedBody::File(body))
+			},
+			BodyConfig::Typed(TypedBody::Json { content }) => BodyConfig::Typed(TypedBody::Json { content: replace(content) }),
+			BodyConfig::Typed(TypedBody::Form { fields }) => BodyConfig::Typed(TypedBody::Form { fields: replace(fields) }),
+			BodyConfig::Typed(TypedBody::Multipart { mut parts }) => {
+				for (k, v) in vars {
+					let k_var = format!("${{{k}}}");
+					for part in parts.iter_mut() {
+						for x in [&mut part.value, &mut part.file, &mut part.filename, &mut part.content_type].into_iter().flatten() {
+							*x = x.replace(&k_var, v);
+						}
+					}
+				}
+				for part in parts.iter_mut() {
+					if let (Some(dir), Some(file)) = (part.dir.take(), &part.file) {
+						part.file = Some(relative(&dir, file));
+					}
+				}
+				BodyConfig::Typed(TypedBody::Multipart { parts })
+			},
+		}
+	}
+}
+
+pub(super) fn relative(dir: &std::path::Path, file: &str) -> String {
+	// leave paths coming from variables alone, bodies resolve them again once filled
+	if file.starts_with("${") || std::path::Path::new(file).is_absolute() {
+		return file.to_string();
+	}
+	dir.join(file).to_string_lossy().to_string()
+}
+
+fn form(fields: &toml::Table) -> String {
+	fields.iter()
+		.map(|(k, v)| format!("{}={}", urlencode(k), urlencode(&stringify_toml(v))))
+		.collect::<Vec<_>>()
+		.join("&")
+}
+
+fn multipart(parts: &[PartConfig], boundary: &str) -> Result<Vec<u8>, PostWomanError> {
+	let mut out = Vec::new();
+	for part in parts {
+		out.extend(format!("--{boundary}\r\nContent-Disposition: form-data; name=\"{}\"", quote(&part.name)).into_bytes());
+		let content = match (&part.value, &part.file) {
+			(Some(value), None) => {
+				if let Some(ref filename) = part.filename {
+					out.extend(format!("; filename=\"{}\"", quote(filename)).into_bytes());
+				}
+				if let Some(ref content_type) = part.content_type {
+					out.extend(format!("\r\nContent-Type: {content_type}").into_bytes());
+				}
+				value.as_bytes().to_vec()
+			},
+			(None, Some(file)) => {
+				let path = std::path::Path::new(file);
+				let content = std::fs::read(path)
+					.map_err(|e| PostWomanError::InvalidBody(format!("could not read part '{}' file {file:?}: {e}", part.name)))?;
+				let filename = part.filename.clone()
+					.unwrap_or_else(|| path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or_default());
+				let content_type = part.content_type.as_deref().unwrap_or_else(|| guess_mime(path));
+				out.extend(format!("; filename=\"{}\"\r\nContent-Type: {content_type}", quote(&filename)).into_bytes());
+				content
+			},
+			_ => return Err(PostWomanError::InvalidBody(format!("part '{}' needs exactly one of `value` or `file`", part.name))),
+		};
+		out.extend(b"\r\n\r\n");
+		out.extend(content);
+		out.extend(b"\r\n");
+	}
+	out.extend(format!("--{boundary}--\r\n").into_bytes());
+	Ok(out)
+}
+
+fn quote(s: &str) -> String {
+	s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// just the common ones, anything fancier can set `content_type` explicitly
+fn guess_mime(path: &std::path::Path) -> &'static str {
+	match path.extension().and_then(|x| x.to_str()).map(|x| x.to_lowercase()).as_deref() {
+		Some("json") => "application/json",
+		Some("txt") => "text/plain",
+		Some("html") | Some("htm") => "text/html",
+		Some("csv") => "text/csv",
+		Some("xml") => "application/xml",
+		Some("pdf") => "application/pdf",
+		Some("zip") => "application/zip",
+		Some("png") => "image/png",
+		Some("jpg") | Some("jpeg") => "image/jpeg",
+		Some("gif") => "image/gif",
+		Some("svg") => "image/svg+xml",
+		_ => "application/octet-stream",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn body(raw: &str) -> BodyConfig {
+		toml::from_str::<toml::Table>(&format!("body = {raw}")).expect("valid toml")["body"]
+			.clone()
+			.try_into()
+			.expect("valid body")
+	}
+
+	fn filled(mut body: BodyConfig, vars: &[(&str, &str)]) -> BodyConfig {
+		body.relative_to(std::path::Path::new("collections"));
+		body.fill_vars(&vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+	}
+
+	#[test]
+	fn file_key_alone_is_json() {
+		assert!(matches!(body(r#"{ file = "x.json" }"#), BodyConfig::Json(_)));
+		assert!(matches!(body(r#"{ type = "file", file = "x.json", raw = true }"#), BodyConfig::Typed(TypedBody::File(_))));
+	}
+
+	#[test]
+	fn resolves_file_from_variables_once_filled() {
+		let file = |body: BodyConfig| match body {
+			BodyConfig::Typed(TypedBody::File(body)) => body.file,
+			other => panic!("expected file body, got {other:?}"),
+		};
+		let sep = std::path::MAIN_SEPARATOR;
+		assert_eq!(file(filled(body(r#"{ type = "file", file = "a.json" }"#), &[])), format!("collections{sep}a.json"));
+		assert_eq!(file(filled(body(r#"{ type = "file", file = "${DIR}/a.json" }"#), &[("DIR", "payloads")])), format!("collections{sep}payloads/a.json"));
+		assert_eq!(file(filled(body(r#"{ type = "file", file = "${DIR}/a.json" }"#), &[("DIR", "/tmp")])), "/tmp/a.json");
+	}
+
+	#[test]
+	fn resolves_parts_from_variables_once_filled() {
+		let raw = r#"{ type = "multipart", parts = [ { name = "a", file = "${NAME}.png" }, { name = "b", value = "${NAME}" } ] }"#;
+		let BodyConfig::Typed(TypedBody::Multipart { parts }) = filled(body(raw), &[("NAME", "avatar")]) else { panic!("expected multipart body") };
+		assert_eq!(parts[0].file.as_deref(), Some(format!("collections{}avatar.png", std::path::MAIN_SEPARATOR).as_str()));
+		assert_eq!(parts[1].value.as_deref(), Some("avatar"));
+	}
+}
@@ -1,4 +1,5 @@
 use crate::ext::FillableFromEnvironment;
+use crate::APP_USER_AGENT;
 
 
 #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
@@ -13,22 +14,194 @@ pub struct ClientConfig {
 	pub redirects: Option<usize>,
 	/// accept invalid SSL certificates, defaults to false (be careful: this is dangerous!)
 	pub accept_invalid_certs: Option<bool>,
+	/// authentication for all routes, unless they specify their own
+	pub auth: Option<super::AuthConfig>,
+	/// obtain a bearer token for all routes which don't specify their own auth
+	pub oauth2: Option<super::OAuth2Config>,
+	/// keep cookies set by responses and send them back on following requests, defaults to false
+	pub cookies: Option<bool>,
+	/// load and store cookies in this Netscape cookies.txt file, relative to collection. implies `cookies`
+	pub cookie_jar: Option<String>,
+	/// retry failed requests, routes can override single options
+	pub retry: Option<super::RetryConfig>,
+	/// http version to use: "1.1", "2" (negotiated via ALPN, fails if server refuses) or "h2c" (HTTP/2 with prior knowledge)
+	pub http_version: Option<HttpVersion>,
+	/// client certificates, trusted roots and pins, routes can override single options
+	pub tls: Option<super::TlsConfig>,
+	/// send requests through this proxy, either http, https or socks5 url
+	pub proxy: Option<String>,
+	/// comma separated hosts or domains which shouldn't go through `proxy`
+	pub no_proxy: Option<String>,
+	/// username to authenticate with proxy
+	pub proxy_username: Option<String>,
+	/// password to authenticate with proxy
+	pub proxy_password: Option<String>,
+	/// use proxies from HTTP_PROXY, HTTPS_PROXY and NO_PROXY env variables when no `proxy` is set, defaults to true
+	pub system_proxy: Option<bool>,
+	/// max requests of this collection in flight at the same time during parallel runs, unlimited by default
+	pub max_concurrency: Option<usize>,
+	/// max requests per second sent to each host by this collection, unlimited by default
+	pub rate_limit: Option<f64>,
+	/// cookies shared during a run, set up by runner when cookies are enabled
+	#[serde(skip)]
+	pub jar: Option<std::sync::Arc<super::CookieJar>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum HttpVersion {
+	#[serde(rename = "1.1")]
+	Http1,
+	#[serde(rename = "2")]
+	Http2,
+	#[serde(rename = "h2c")]
+	H2c,
+}
+
+impl ClientConfig {
+	/// http client configured with these options
+	pub fn client(&self) -> Result<reqwest::Client, crate::PostWomanError> {
+		if let Some(rate) = self.rate_limit {
+			super::rate_interval(rate)?;
+		}
+		let mut builder = reqwest::Client::builder()
+			.user_agent(self.user_agent.as_deref().unwrap_or(APP_USER_AGENT))
+			.timeout(std::time::Duration::from_secs(self.timeout.unwrap_or(30)))
+			.redirect(self.redirect_policy())
+			.danger_accept_invalid_certs(self.accepts_invalid_certs())
+			.dns_resolver(std::sync::Arc::new(super::timings::TimingResolver))
+			.connector_layer(super::timings::TimingLayer);
+		if let Some(ref jar) = self.jar {
+			builder = builder.cookie_provider(jar.clone());
+		}
+		if let Some(ref url) = self.proxy {
+			let mut proxy = reqwest::Proxy::all(url)?
+				.no_proxy(self.no_proxy.as_deref().and_then(reqwest::NoProxy::from_string));
+			if let Some(ref username) = self.proxy_username {
+				proxy = proxy.basic_auth(username, self.proxy_password.as_deref().unwrap_or_default());
+			}
+			builder = builder.proxy(proxy);
+		} else if !self.system_proxy.unwrap_or(true) {
+			builder = builder.no_proxy();
+		}
+		match self.http_version {
+			None | Some(HttpVersion::Http2) => {},
+			Some(HttpVersion::Http1) => builder = builder.http1_only(),
+			Some(HttpVersion::H2c) => builder = builder.http2_prior_knowledge(),
+		}
+		if let Some(ref tls) = self.tls {
+			builder = tls.configure(builder, self.accepts_invalid_certs(), self.http_version)?;
+		}
+		Ok(builder.build()?)
+	}
+
+	fn redirect_policy(&self) -> reqwest::redirect::Policy {
+		let Some(max) = self.redirects else { return reqwest::redirect::Policy::none() };
+		if self.tls.as_ref().is_none_or(|x| x.pin.is_none()) {
+			return reqwest::redirect::Policy::limited(max);
+		}
+		// every https hop gets pinned while handshaking, but plain http would skip pins altogether
+		reqwest::redirect::Policy::custom(move |attempt| {
+			if attempt.url().scheme() != "https" {
+				let url = attempt.url().to_string();
+				attempt.error(format!("refusing to follow redirect to {url} without pinned tls"))
+			} else if attempt.previous().len() > max {
+				attempt.error("too many redirects")
+			} else {
+				attempt.follow()
+			}
+		})
+	}
+
+	/// values which should never show up in outputs
+	pub fn secrets(&self) -> Vec<String> {
+		self.auth.iter()
+			.flat_map(|x| x.secrets())
+			.chain(self.oauth2.iter().flat_map(|x| x.secrets()))
+			.chain(self.tls.iter().flat_map(|x| x.secrets()))
+			.chain(self.proxy_password.iter().filter(|x| !x.is_empty()).cloned())
+			.collect()
+	}
+
+	/// same config, with credentials replaced, safe to display
+	pub fn redacted(&self) -> Self {
+		ClientConfig {
+			auth: self.auth.as_ref().map(|x| x.redacted()),
+			oauth2: self.oauth2.as_ref().map(|x| x.redacted()),
+			tls: self.tls.as_ref().map(|x| x.redacted()),
+			proxy_password: self.proxy_password.as_ref().map(|_| super::REDACTED.to_string()),
+			..self.clone()
+		}
+	}
+
+	/// these options with route overrides applied, None if route can use collection client as it is
+	pub fn for_route(&self, endpoint: &super::EndpointConfig) -> Option<ClientConfig> {
+		if endpoint.tls.is_none() && endpoint.http_version.is_none() {
+			return None;
+		}
+		Some(ClientConfig {
+			tls: match (&self.tls, &endpoint.tls) {
+				(Some(base), Some(route)) => Some(base.merge(route)),
+				(base, route) => route.clone().or(base.clone()),
+			},
+			http_version: endpoint.http_version.or(self.http_version),
+			..self.clone()
+		})
+	}
+
+	/// whether certificates are left unchecked, tls options win over client ones
+	pub fn accepts_invalid_certs(&self) -> bool {
+		self.tls.as_ref().and_then(|x| x.accept_invalid_certs).or(self.accept_invalid_certs).unwrap_or(false)
+	}
+
+	/// whether routes should share cookies
+	pub fn keeps_cookies(&self) -> bool {
+		self.cookies.unwrap_or(false) || self.cookie_jar.is_some()
+	}
+
+	/// make cookie jar path relative to given directory, unless already absolute
+	pub fn relative_to(&mut self, dir: &std::path::Path) {
+		if let Some(ref jar) = self.cookie_jar {
+			self.cookie_jar = Some(super::body::relative(dir, jar));
+		}
+		if let Some(ref mut tls) = self.tls {
+			tls.relative_to(dir);
+		}
+	}
 }
 
 impl FillableFromEnvironment for ClientConfig {
-	fn fill(mut self, env: &toml::Table) -> Self {
-		let vars = Self::default_vars(env);
+	fn fill_vars(mut self, vars: &crate::ext::Vars) -> Self {
 
 		for (k, v) in vars {
 			let k_var = format!("${{{k}}}");
 
 			if let Some(base) = self.base {
-				self.base = Some(base.replace(&k_var, &v));
+				self.base = Some(base.replace(&k_var, v));
 			}
 
 			if let Some(user_agent) = self.user_agent {
-				self.user_agent = Some(user_agent.replace(&k_var, &v));
+				self.user_agent = Some(user_agent.replace(&k_var, v));
 			}
+
+			if let Some(cookie_jar) = self.cookie_jar {
+				self.cookie_jar = Some(cookie_jar.replace(&k_var, v));
+			}
+
+			for field in [&mut self.proxy, &mut self.no_proxy, &mut self.proxy_username, &mut self.proxy_password].into_iter().flatten() {
+				*field = field.replace(&k_var, v);
+			}
+		}
+
+		if let Some(auth) = self.auth {
+			self.auth = Some(auth.fill_vars(vars));
+		}
+
+		if let Some(oauth2) = self.oauth2 {
+			self.oauth2 = Some(oauth2.fill_vars(vars));
+		}
+
+		if let Some(tls) = self.tls {
+			self.tls = Some(tls.fill_vars(vars));
 		}
 
 		self
@@ -0,0 +1,310 @@
+use reqwest::header::HeaderValue;
+
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File\n# written by postwoman, can be shared with curl -b/-c\n\n";
+// curl marks httponly cookies prefixing their domain
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cookie {
+	domain: String,
+	/// sent to subdomains too, false for host only cookies
+	include_subdomains: bool,
+	path: String,
+	secure: bool,
+	http_only: bool,
+	/// unix timestamp, 0 for session cookies
+	expires: i64,
+	name: String,
+	value: String,
+}
+
+impl Cookie {
+	fn expired(&self, now: i64) -> bool {
+		self.expires != 0 && self.expires <= now
+	}
+
+	fn matches(&self, url: &reqwest::Url) -> bool {
+		let Some(host) = url.host_str() else { return false };
+		let host = host.to_lowercase();
+		let domain_ok = host == self.domain
+			|| (self.include_subdomains && host.ends_with(&format!(".{}", self.domain)));
+		let path = url.path();
+		let path_ok = path == self.path
+			|| (path.starts_with(&self.path) && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+		domain_ok && path_ok && (!self.secure || url.scheme() == "https")
+	}
+
+	/// parse a Set-Cookie header received from given url, None if invalid or not allowed for it
+	fn parse(header: &str, url: &reqwest::Url) -> Option<Self> {
+		let host = url.host_str()?.to_lowercase();
+		let mut attributes = header.split(';');
+		let (name, value) = attributes.next()?.split_once('=')?;
+		let name = name.trim();
+		if name.is_empty() { return None };
+
+		let mut cookie = Cookie {
+			domain: host.clone(),
+			include_subdomains: false,
+			path: default_path(url.path()),
+			secure: false,
+			http_only: false,
+			expires: 0,
+			name: name.to_string(),
+			value: value.trim().trim_matches('"').to_string(),
+		};
+
+		let mut max_age = None;
+		for attribute in attributes {
+			let (k, v) = attribute.split_once('=').unwrap_or((attribute, ""));
+			let v = v.trim();
+			match k.trim().to_lowercase().as_str() {
+				"domain" if !v.is_empty() => {
+					let domain = v.trim_start_matches('.').to_lowercase();
+					// servers can't set cookies for other sites
+					if host != domain && !host.ends_with(&format!(".{domain}")) {
+						return None;
+					}
+					cookie.domain = domain;
+					cookie.include_subdomains = true;
+				},
+				"path" if v.starts_with('/') => cookie.path = v.to_string(),
+				"secure" => cookie.secure = true,
+				"httponly" => cookie.http_only = true,
+				"max-age" => max_age = v.parse::<i64>().ok(),
+				"expires" => if let Some(expires) = parse_date(v) { cookie.expires = expires },
+				_ => {},
+			}
+		}
+
+		// max-age wins over expires, non positive values mean delete it now
+		if let Some(max_age) = max_age {
+			cookie.expires = if max_age <= 0 { 1 } else { chrono::Utc::now().timestamp() + max_age };
+		}
+
+		Some(cookie)
+	}
+
+	/// one line of a Netscape cookies.txt file
+	fn line(&self) -> String {
+		let domain = if self.include_subdomains { format!(".{}", self.domain) } else { self.domain.clone() };
+		format!(
+			"{}{domain}\t{}\t{}\t{}\t{}\t{}\t{}",
+			if self.http_only { HTTP_ONLY_PREFIX } else { "" },
+			flag(self.include_subdomains),
+			self.path,
+			flag(self.secure),
+			self.expires,
+			self.name,
+			self.value,
+		)
+	}
+
+	fn from_line(line: &str) -> Option<Self> {
+		let (http_only, line) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+			Some(rest) => (true, rest),
+			None => (false, line),
+		};
+		if line.starts_with('#') || line.trim().is_empty() { return None };
+		let fields: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('\t').collect();
+		let [domain, include_subdomains, path, secure, expires, name, value] = fields.as_slice() else { return None };
+		Some(Cookie {
+			domain: domain.trim_start_matches('.').to_lowercase(),
+			include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE"),
+			path: path.to_string(),
+			secure: secure.eq_ignore_ascii_case("TRUE"),
+			http_only,
+			expires: expires.parse().ok()?,
+			name: name.to_string(),
+			value: value.to_string(),
+		})
+	}
+}
+
+/// cookies shared by all routes using it, optionally stored on disk as Netscape cookies.txt
+#[derive(Debug, Default)]
+pub struct CookieJar {
+	cookies: std::sync::RwLock<Vec<Cookie>>,
+}
+
+impl CookieJar {
+	/// load cookies from a cookies.txt file, an empty jar if it doesn't exist yet
+	pub fn load(path: &std::path::Path) -> std::io::Result<Self> {
+		let raw = match std::fs::read_to_string(path) {
+			Ok(x) => x,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+			Err(e) => return Err(e),
+		};
+		let now = chrono::Utc::now().timestamp();
+		let cookies = raw.lines()
+			.filter_map(Cookie::from_line)
+			.filter(|x| !x.expired(now))
+			.collect();
+		Ok(CookieJar { cookies: std::sync::RwLock::new(cookies) })
+	}
+
+	/// write all cookies still valid into a cookies.txt file, returning how many were saved
+	pub fn save(&self, path: &std::path::Path) -> std::io::Result<usize> {
+		let now = chrono::Utc::now().timestamp();
+		let cookies = self.cookies.read().expect("cookie jar poisoned");
+		let mut out = NETSCAPE_HEADER.to_string();
+		let mut count = 0;
+		for cookie in cookies.iter().filter(|x| !x.expired(now)) {
+			out.push_str(&cookie.line());
+			out.push('\n');
+			count += 1;
+		}
+		if let Some(parent) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+			std::fs::create_dir_all(parent)?;
+		}
+		std::fs::write(path, out)?;
+		Ok(count)
+	}
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+	fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &reqwest::Url) {
+		let now = chrono::Utc::now().timestamp();
+		let mut cookies = self.cookies.write().expect("cookie jar poisoned");
+		for header in cookie_headers {
+			let Some(cookie) = header.to_str().ok().and_then(|x| Cookie::parse(x, url)) else { continue };
+			cookies.retain(|x| !(x.domain == cookie.domain && x.path == cookie.path && x.name == cookie.name));
+			if !cookie.expired(now) {
+				cookies.push(cookie);
+			}
+		}
+	}
+
+	fn cookies(&self, url: &reqwest::Url) -> Option<HeaderValue> {
+		let now = chrono::Utc::now().timestamp();
+		let cookies = self.cookies.read().expect("cookie jar poisoned");
+		let mut matching: Vec<&Cookie> = cookies.iter()
+			.filter(|x| !x.expired(now) && x.matches(url))
+			.collect();
+		if matching.is_empty() { return None };
+		// more specific paths go first
+		matching.sort_by_key(|x| std::cmp::Reverse(x.path.len()));
+		let header = matching.iter()
+			.map(|x| format!("{}={}", x.name, x.value))
+			.collect::<Vec<_>>()
+			.join("; ");
+		HeaderValue::from_str(&header).ok()
+	}
+}
+
+fn flag(value: bool) -> &'static str {
+	if value { "TRUE" } else { "FALSE" }
+}
+
+// directory of request path, as per RFC 6265
+fn default_path(path: &str) -> String {
+	match path.rfind('/') {
+		Some(0) | None => "/".to_string(),
+		Some(i) => path[..i].to_string(),
+	}
+}
+
+// servers still send all sorts of date formats
+fn parse_date(date: &str) -> Option<i64> {
+	if let Ok(x) = chrono::DateTime::parse_from_rfc2822(date) {
+		return Some(x.timestamp());
+	}
+	["%a, %d-%b-%Y %H:%M:%S GMT", "%A, %d-%b-%y %H:%M:%S GMT", "%A, %d-%b-%Y %H:%M:%S GMT", "%a, %d %b %Y %H:%M:%S GMT"]
+		.iter()
+		.find_map(|fmt| chrono::NaiveDateTime::parse_from_str(date, fmt).ok())
+		.map(|x| x.and_utc().timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn url(x: &str) -> reqwest::Url {
+		reqwest::Url::parse(x).expect("valid url")
+	}
+
+	#[test]
+	fn parses_host_only_cookie() {
+		let cookie = Cookie::parse("sid=abc; Path=/api; Secure; HttpOnly", &url("https://Example.com/api/login")).expect("valid cookie");
+		assert_eq!(cookie, Cookie {
+			domain: "example.com".into(),
+			include_subdomains: false,
+			path: "/api".into(),
+			secure: true,
+			http_only: true,
+			expires: 0,
+			name: "sid".into(),
+			value: "abc".into(),
+		});
+	}
+
+	#[test]
+	fn parses_domain_and_default_path() {
+		let cookie = Cookie::parse("a=\"1\"; Domain=.example.com", &url("http://api.example.com/v1/users/me")).expect("valid cookie");
+		assert_eq!(cookie.domain, "example.com");
+		assert!(cookie.include_subdomains);
+		assert_eq!(cookie.path, "/v1/users");
+		assert_eq!(cookie.value, "1");
+		assert!(cookie.matches(&url("http://www.example.com/v1/users/other")));
+		assert!(!cookie.matches(&url("http://example.com/v1/usersx")));
+		assert!(!cookie.matches(&url("http://notexample.com/v1/users")));
+	}
+
+	#[test]
+	fn rejects_cookies_for_other_sites() {
+		assert_eq!(Cookie::parse("a=1; Domain=evil.com", &url("http://example.com/")), None);
+		assert_eq!(Cookie::parse("a=1; Domain=sub.example.com", &url("http://example.com/")), None);
+		assert_eq!(Cookie::parse("=1", &url("http://example.com/")), None);
+		assert_eq!(Cookie::parse("novalue", &url("http://example.com/")), None);
+	}
+
+	#[test]
+	fn parses_expiry() {
+		let u = url("http://example.com/");
+		let cookie = Cookie::parse("a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT", &u).expect("valid cookie");
+		assert_eq!(cookie.expires, 1445412480);
+		let cookie = Cookie::parse("a=1; expires=Wednesday, 21-Oct-15 07:28:00 GMT", &u).expect("valid cookie");
+		assert_eq!(cookie.expires, 1445412480);
+		assert!(cookie.expired(chrono::Utc::now().timestamp()));
+
+		// max-age wins, whatever order
+		let now = chrono::Utc::now().timestamp();
+		let cookie = Cookie::parse("a=1; Max-Age=60; Expires=Wed, 21 Oct 2015 07:28:00 GMT", &u).expect("valid cookie");
+		assert!((now + 60..=now + 61).contains(&cookie.expires));
+		assert!(!cookie.expired(now));
+		let cookie = Cookie::parse("a=1; Max-Age=0", &u).expect("valid cookie");
+		assert!(cookie.expired(now));
+	}
+
+	#[test]
+	fn reads_netscape_lines() {
+		let cookie = Cookie::from_line(".example.com\tTRUE\t/\tFALSE\t1445412480\ta\t1").expect("valid line");
+		assert_eq!(cookie.domain, "example.com");
+		assert!(cookie.include_subdomains);
+		assert!(!cookie.secure);
+		assert!(!cookie.http_only);
+		assert_eq!(cookie.expires, 1445412480);
+		assert_eq!((cookie.name.as_str(), cookie.value.as_str()), ("a", "1"));
+
+		let cookie = Cookie::from_line("#HttpOnly_example.com\tFALSE\t/api\tTRUE\t0\tsid\tabc\r\n").expect("valid line");
+		assert!(cookie.http_only);
+		assert!(cookie.secure);
+		assert!(!cookie.include_subdomains);
+		assert_eq!(cookie.expires, 0);
+		assert_eq!(cookie.value, "abc");
+	}
+
+	#[test]
+	fn skips_comments_and_broken_lines() {
+		assert_eq!(Cookie::from_line("# Netscape HTTP Cookie File"), None);
+		assert_eq!(Cookie::from_line(""), None);
+		assert_eq!(Cookie::from_line("example.com\tFALSE\t/\tFALSE\tsoon\ta\t1"), None);
+		assert_eq!(Cookie::from_line("example.com\tFALSE\t/\tFALSE\t0\ta"), None);
+	}
+
+	#[test]
+	fn lines_round_trip() {
+		for line in ["#HttpOnly_.example.com\tTRUE\t/\tTRUE\t1445412480\tsid\tabc", "example.com\tFALSE\t/api\tFALSE\t0\ta\t1"] {
+			assert_eq!(Cookie::from_line(line).expect("valid line").line(), line);
+		}
+	}
+}
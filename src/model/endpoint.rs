@@ -1,15 +1,15 @@
 use std::str::FromStr;
 
-use base64::{prelude::BASE64_STANDARD, Engine};
 use http::method::InvalidMethod;
 use http::{HeaderMap, HeaderName, HeaderValue};
-use jaq_interpret::FilterT;
 
 use crate::errors::InvalidHeaderError;
 use crate::{PostWomanError, APP_USER_AGENT};
 
-use crate::ext::{stringify_json, FillableFromEnvironment, StringOr};
-use super::{ExtractorConfig, ClientConfig};
+use crate::ext::{FillableFromEnvironment, StringOr};
+use super::auth::{redact, Credential, REDACTED};
+use super::timings::{measure, Marks, Timings};
+use super::{AssertConfig, AuthConfig, BodyConfig, BufferedResponse, ClientConfig, ExtractorConfig, TokenStore};
 
 
 #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
@@ -24,14 +24,28 @@ pub struct EndpointConfig {
 	pub query: Option<Vec<String>>,
 	/// headers for request, array of "key: value" pairs
 	pub headers: Option<Vec<String>>,
-	/// body, optional string
-	pub body: Option<StringOr<toml::Table>>,
+	/// body, either a plain string, a table sent as json or a typed body (form, multipart)
+	pub body: Option<StringOr<BodyConfig>>,
 	/// expected error code, will fail if different, defaults to 200
 	pub status: Option<u16>,
+	/// authentication for this route, overrides client one
+	pub auth: Option<AuthConfig>,
+	/// tls options for this route, each one overrides the client one
+	pub tls: Option<super::TlsConfig>,
+	/// retry options for this route, each one overrides the client one
+	pub retry: Option<super::RetryConfig>,
+	/// http version for this route, overrides client one
+	pub http_version: Option<super::HttpVersion>,
+	/// checks to run on response, all are evaluated and failures reported together
+	pub assert: Option<AssertConfig>,
 	/// response extractor
 	pub extract: Option<StringOr<ExtractorConfig>>,
 	/// expected result, will fail if different when provided
 	pub expect: Option<String>,
+	/// store extracted result into given variable, or run a table of variable = extractor on response
+	pub capture: Option<StringOr<indexmap::IndexMap<String, StringOr<ExtractorConfig>>>>,
+	/// routes which must succeed before this one runs, either names in same collection or full names like `other:route`
+	pub depends_on: Option<Vec<String>>,
 }
 
 impl EndpointConfig {
@@ -39,7 +53,26 @@ impl EndpointConfig {
 		match &self.body {
 			None => Ok("".to_string()),
 			Some(StringOr::Str(x)) => Ok(x.clone()),
-			Some(StringOr::T(json)) => Ok(serde_json::to_string(&json)?),
+			Some(StringOr::T(body)) => body.text(),
+		}
+	}
+
+	/// encoded body to send, with its default Content-Type if it has one
+	pub fn payload(&self) -> Result<(Option<String>, Vec<u8>), PostWomanError> {
+		match &self.body {
+			None => Ok((None, Vec::new())),
+			Some(StringOr::Str(x)) => Ok((None, x.clone().into_bytes())),
+			Some(StringOr::T(body)) => body.payload(),
+		}
+	}
+
+	/// resolve paths of files referenced by this route relative to its collection directory
+	pub fn relative_to(&mut self, dir: &std::path::Path) {
+		if let Some(StringOr::T(ref mut body)) = self.body {
+			body.relative_to(dir);
+		}
+		if let Some(ref mut tls) = self.tls {
+			tls.relative_to(dir);
 		}
 	}
 
@@ -75,120 +108,262 @@ impl EndpointConfig {
 		url
 	}
 
-	pub async fn execute(self, opts: &ClientConfig) -> Result<String, PostWomanError> {
-		let body = self.body()?;
+	/// perform request with given client, built from `opts`, keeping both request and response around without checking anything
+	pub async fn send(&self, opts: &ClientConfig, client: &reqwest::Client) -> Result<Exchange, PostWomanError> {
+		let (content_type, body) = self.payload()?;
 		let method = self.method()?;
-		let headers = self.headers()?;
+		let mut headers = self.headers()?;
+		if let Some(content_type) = content_type {
+			if !headers.contains_key(http::header::CONTENT_TYPE) {
+				headers.insert(http::header::CONTENT_TYPE, HeaderValue::from_str(&content_type).map_err(InvalidHeaderError::from)?);
+			}
+		}
 		let url = self.url(opts.base.as_deref());
+		let user_agent = opts.user_agent.as_deref().unwrap_or(APP_USER_AGENT);
 
-		let client = reqwest::Client::builder()
-			.user_agent(opts.user_agent.as_deref().unwrap_or(APP_USER_AGENT))
-			.timeout(std::time::Duration::from_secs(opts.timeout.unwrap_or(30)))
-			.redirect(opts.redirects.map(reqwest::redirect::Policy::limited).unwrap_or(reqwest::redirect::Policy::none()))
-			.danger_accept_invalid_certs(opts.accept_invalid_certs.unwrap_or(false))
-			.build()?;
-
-
-		let res = client
-			.request(method, url)
-			.headers(headers)
-			.body(body)
-			.send()
-			.await?;
-
-		if res.status().as_u16() != self.status.unwrap_or(200) {
-			return Err(PostWomanError::UnexpectedStatusCode(res));
-		}
-
-		let res = match self.extract.unwrap_or_default() {
-			StringOr::T(ExtractorConfig::Discard) => "".to_string(),
-			StringOr::T(ExtractorConfig::Body) => format_body(res).await?,
-			StringOr::T(ExtractorConfig::Debug) => {
-				// TODO needless double format
-				let res_dbg = format!("{res:#?}");
-				let body = format_body(res).await?; 
-				format!("{res_dbg}\nBody: {body}")
-			},
-			StringOr::T(ExtractorConfig::Header { key }) => res
-				.headers()
-				.get(&key)
-				.ok_or(PostWomanError::HeaderNotFound(key))?
-				.to_str()?
-				.to_string(),
-			StringOr::T(ExtractorConfig::Regex { pattern }) => {
-				let pattern = regex::Regex::new(&pattern)?;
-				let body = format_body(res).await?;
-				pattern.find(&body)
-					.ok_or_else(|| PostWomanError::NoMatch(body.clone()))?
-					.as_str()
-					.to_string()
-			},
-			// bare string defaults to JQL query
-			StringOr::T(ExtractorConfig::JQ { query }) | StringOr::Str(query) => {
-				let json: serde_json::Value = res.json().await?;
-				let selection = jq(&query, json)?;
-				if selection.len() == 1 {
-					stringify_json(&selection[0])
-				} else {
-					serde_json::to_string_pretty(&selection)?
+		let auth = self.auth.as_ref().or(opts.auth.as_ref());
+		let credential = auth.and_then(|x| x.credentials());
+		if let Some(Credential::Header(ref k, ref v)) = credential {
+			headers.insert(
+				HeaderName::from_str(k).map_err(InvalidHeaderError::from)?,
+				HeaderValue::from_str(v).map_err(InvalidHeaderError::from)?,
+			);
+		}
+
+		let mut request = client
+			.request(method.clone(), &url)
+			.headers(headers.clone())
+			.body(body.clone());
+		if let Some(Credential::Query(k, v)) = credential {
+			request = request.query(&[(k, v)]);
+		}
+		let request = request.build()?;
+
+		let url = request.url().clone();
+
+		// client default headers are only added when sending, record them as they will be sent
+		if !headers.contains_key(http::header::USER_AGENT) {
+			headers.insert(http::header::USER_AGENT, HeaderValue::from_str(user_agent).map_err(InvalidHeaderError::from)?);
+		}
+		if let Some(cookies) = opts.jar.as_ref().and_then(|x| reqwest::cookie::CookieStore::cookies(x.as_ref(), &url)) {
+			if !headers.contains_key(http::header::COOKIE) {
+				headers.insert(http::header::COOKIE, cookies);
+			}
+		}
+
+		let started = chrono::Local::now();
+		let before = std::time::Instant::now();
+
+		let marks = std::sync::Arc::new(std::sync::Mutex::new(Marks::default()));
+		let mut res = measure(marks.clone(), client.execute(request)).await?;
+
+		// digest credentials can only be computed after server challenge, so try again answering it
+		if let Some(auth @ AuthConfig::Digest { .. }) = auth {
+			let challenge = res.headers()
+				.get_all(http::header::WWW_AUTHENTICATE)
+				.iter()
+				.filter_map(|x| x.to_str().ok())
+				.find(|x| x.trim_start().starts_with("Digest "));
+			if res.status() == reqwest::StatusCode::UNAUTHORIZED {
+				if let Some(challenge) = challenge {
+					let uri = match url.query() {
+						Some(query) => format!("{}?{query}", url.path()),
+						None => url.path().to_string(),
+					};
+					let answer = auth.digest(challenge, method.as_str(), &uri, &body)?;
+					headers.insert(http::header::AUTHORIZATION, HeaderValue::from_str(&answer).map_err(InvalidHeaderError::from)?);
+					let retry = client
+						.request(method.clone(), url.clone())
+						.headers(headers.clone())
+						.body(body.clone())
+						.build()?;
+					res = measure(marks.clone(), client.execute(retry)).await?;
 				}
-			},
+			}
+		}
+
+		// ALPN silently falls back to HTTP/1.1, but we were asked to test HTTP/2
+		if opts.http_version == Some(super::HttpVersion::Http2) && res.version() != http::Version::HTTP_2 {
+			return Err(PostWomanError::UnexpectedHttpVersion(format!("{:?}", res.version()), "HTTP/2.0".to_string()));
+		}
+
+		let headers_received = std::time::Instant::now();
+
+		let response = BufferedResponse::read(res).await?;
+		let elapsed = before.elapsed();
+		let timings = marks.lock().expect("timing marks poisoned")
+			.timings(before, headers_received, before + elapsed);
+
+		// never keep credentials around in recordings
+		let secrets = auth.map(|x| x.secrets()).unwrap_or_default();
+		let url = redact(url.as_str(), &secrets);
+		let headers = headers.iter()
+			.map(|(k, v)| match v.to_str() {
+				Ok(v) => (k.clone(), HeaderValue::from_str(&redact(v, &secrets)).unwrap_or_else(|_| HeaderValue::from_static(REDACTED))),
+				Err(_) => (k.clone(), v.clone()),
+			})
+			.collect();
+
+		Ok(Exchange {
+			started,
+			request: RecordedRequest { method, url, headers, body },
+			response,
+			timings,
+			elapsed,
+		})
+	}
+
+	/// send this route, authenticating with collection oauth2 token unless it has its own auth
+	pub async fn send_authenticated(
+		&mut self,
+		opts: &ClientConfig,
+		client: &reqwest::Client,
+		tokens: Option<&TokenStore>,
+	) -> Result<Exchange, PostWomanError> {
+		let Some(tokens) = tokens.filter(|_| self.auth.is_none()) else {
+			return self.send(opts, client).await;
 		};
 
+		let token = tokens.token(client, None).await?;
+		self.auth = Some(AuthConfig::Bearer { token: token.clone() });
+		let exchange = self.send(opts, client).await?;
+		if exchange.response.status != reqwest::StatusCode::UNAUTHORIZED {
+			return Ok(exchange);
+		}
+
+		// token may have been revoked before expiring, try once more with a fresh one
+		eprintln!(" ! oauth2 token rejected, refreshing it");
+		let token = tokens.token(client, Some(&token)).await?;
+		self.auth = Some(AuthConfig::Bearer { token });
+		self.send(opts, client).await
+	}
+
+	/// run status check, assertions, extractor and captures on a completed exchange
+	pub fn check(self, exchange: &Exchange) -> Result<EndpointOutput, PostWomanError> {
+		let res = &exchange.response;
+
+		let expected_status = self.status.unwrap_or(200);
+		match self.assert {
+			None if res.status.as_u16() != expected_status =>
+				return Err(PostWomanError::UnexpectedStatusCode(Box::new(res.clone()))),
+			None => {},
+			// status assertions replace plain status check, otherwise it's one more check, and all get reported together
+			Some(ref assert) => {
+				let mut failures = Vec::new();
+				if assert.status.is_none() && res.status.as_u16() != expected_status {
+					failures.push(format!("status {} is not {expected_status}", res.status.as_u16()));
+				}
+				failures.extend(assert.check(res, exchange.elapsed));
+				if !failures.is_empty() {
+					return Err(PostWomanError::FailedAssertions(failures));
+				}
+			},
+		}
+
+		let result = self.extract.unwrap_or_default().extract(res)?;
+
 		if let Some(expected) = self.expect {
-			if expected != res {
-				return Err(PostWomanError::UnexpectedResult(res, expected));
+			if expected != result {
+				return Err(PostWomanError::UnexpectedResult(result, expected));
 			}
 		}
 
-		Ok(res)
+		let mut captures = toml::Table::new();
+		match self.capture {
+			None => {},
+			Some(StringOr::Str(var)) => {
+				captures.insert(var, toml::Value::String(result.clone()));
+			},
+			Some(StringOr::T(extractors)) => {
+				for (var, extractor) in extractors {
+					captures.insert(var, toml::Value::String(extractor.extract(res)?));
+				}
+			},
+		}
+
+		Ok(EndpointOutput { result, captures })
 	}
 }
 
+/// request as it was sent
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+	pub method: reqwest::Method,
+	pub url: String,
+	pub headers: HeaderMap,
+	pub body: Vec<u8>,
+}
+
+/// full request and response of a route, with timings
+#[derive(Debug)]
+pub struct Exchange {
+	pub started: chrono::DateTime<chrono::Local>,
+	pub request: RecordedRequest,
+	pub response: BufferedResponse,
+	/// time spent in each phase of request
+	pub timings: Timings,
+	/// total time, including reading the whole body
+	pub elapsed: std::time::Duration,
+}
+
+/// what a successful request leaves behind
+#[derive(Debug, Default)]
+pub struct EndpointOutput {
+	/// output of route extractor
+	pub result: String,
+	/// variables captured from response, to be used by routes running afterwards
+	pub captures: toml::Table,
+}
+
 impl FillableFromEnvironment for EndpointConfig {
-	fn fill(mut self, env: &toml::Table) -> Self {
-		let vars = Self::default_vars(env);
+	fn fill_vars(mut self, vars: &crate::ext::Vars) -> Self {
 
 		for (k, v) in vars {
 			let k_var = format!("${{{k}}}");
-			self.path = self.path.replace(&k_var, &v);
+			self.path = self.path.replace(&k_var, v);
 			if let Some(method) = self.method {
-				self.method = Some(method.replace(&k_var, &v));
+				self.method = Some(method.replace(&k_var, v));
 			}
-			if let Some(b) = self.body {
-				match b {
-					StringOr::Str(body) => {
-						self.body = Some(StringOr::Str(body.replace(&k_var, &v)));
-					},
-					StringOr::T(json) => {
-						let wrap = toml::Value::Table(json.clone());
-						let toml::Value::Table(out) = replace_recursive(wrap, &k_var, &v)
-						else { unreachable!("we put in a table, we get out a table") };
-						self.body = Some(StringOr::T(out));
-					},
-				}
+			if let Some(StringOr::Str(body)) = self.body {
+				self.body = Some(StringOr::Str(body.replace(&k_var, v)));
 			}
 			if let Some(query) = self.query {
 				self.query = Some(
 					query.into_iter()
-						.map(|x| x.replace(&k_var, &v))
+						.map(|x| x.replace(&k_var, v))
 						.collect()
 				);
 			}
 			if let Some(headers) = self.headers {
 				self.headers = Some(
 					headers.into_iter()
-						.map(|x| x.replace(&k_var, &v))
+						.map(|x| x.replace(&k_var, v))
 						.collect()
 				);
 			}
 		}
+
+		if let Some(StringOr::T(body)) = self.body {
+			self.body = Some(StringOr::T(body.fill_vars(vars)));
+		}
+
+		if let Some(assert) = self.assert {
+			self.assert = Some(assert.fill_vars(vars));
+		}
+
+		if let Some(auth) = self.auth {
+			self.auth = Some(auth.fill_vars(vars));
+		}
+
+		if let Some(tls) = self.tls {
+			self.tls = Some(tls.fill_vars(vars));
+		}
 		
 		self
 	}
 }
 
-fn replace_recursive(element: toml::Value, from: &str, to: &str) -> toml::Value {
+pub(super) fn replace_recursive(element: toml::Value, from: &str, to: &str) -> toml::Value {
 	match element {
 		toml::Value::Float(x) => toml::Value::Float(x),
 		toml::Value::Integer(x) => toml::Value::Integer(x),
@@ -212,40 +387,3 @@ fn replace_recursive(element: toml::Value, from: &str, to: &str) -> toml::Value
 		},
 	}
 }
-
-async fn format_body(res: reqwest::Response) -> Result<String, PostWomanError> {
-	match res.headers().get("Content-Type") {
-		None => Ok(res.text().await?),
-		Some(v) => {
-			let content_type = v.to_str()?;
-			if content_type.starts_with("application/json") {
-				Ok(serde_json::to_string_pretty(&res.json::<serde_json::Value>().await?)?)
-			} else if content_type.starts_with("text/plain") || content_type.starts_with("text/html") {
-				Ok(res.text().await?)
-			} else {
-				Ok(format!("base64({})\n", BASE64_STANDARD.encode(res.bytes().await?)))
-			}
-		},
-	}
-}
-
-fn jq(query: &str, value: serde_json::Value) -> Result<Vec<serde_json::Value>, PostWomanError> {
-	// TODO am i not getting jaq api? or is it just this weird????
-	let mut defs = jaq_interpret::ParseCtx::new(Vec::new());
-	let (filter, errs) = jaq_parse::parse(query, jaq_parse::main());
-	let Some(filter) = filter else {
-		return Err(PostWomanError::JQError(
-			errs.into_iter().map(|x| format!("{x:?}")).collect::<Vec<String>>().join(", ")
-		));
-	};
-	let out: Vec<serde_json::Value> = defs
-		.compile(filter)
-		.run((
-			jaq_interpret::Ctx::new([], &jaq_interpret::RcIter::new(core::iter::empty())),
-			jaq_interpret::Val::from(value)
-		))
-		.filter_map(|x| Some(x.ok()?.into()))
-		.collect();
-
-	Ok(out)
-}
@@ -1,3 +1,8 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use jaq_interpret::FilterT;
+
+use crate::PostWomanError;
+use crate::ext::{stringify_json, StringOr};
 
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
@@ -10,3 +15,124 @@ pub enum ExtractorConfig {
 	Regex { pattern: String },
 	Header { key: String },
 }
+
+/// response fully read into memory, so that more than one extractor can look at it
+#[derive(Clone)]
+pub struct BufferedResponse {
+	pub url: String,
+	pub status: reqwest::StatusCode,
+	pub version: http::Version,
+	pub headers: http::HeaderMap,
+	pub body: Vec<u8>,
+}
+
+// mimic reqwest::Response debug format, body is shown separately by the debug extractor
+impl std::fmt::Debug for BufferedResponse {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Response")
+			.field("url", &self.url)
+			.field("status", &self.status.as_u16())
+			.field("version", &self.version)
+			.field("headers", &self.headers)
+			.finish()
+	}
+}
+
+impl BufferedResponse {
+	pub async fn read(res: reqwest::Response) -> Result<Self, PostWomanError> {
+		Ok(BufferedResponse {
+			url: res.url().to_string(),
+			status: res.status(),
+			version: res.version(),
+			headers: res.headers().clone(),
+			body: res.bytes().await?.to_vec(),
+		})
+	}
+
+	pub fn text(&self) -> String {
+		String::from_utf8_lossy(&self.body).to_string()
+	}
+
+	pub fn json(&self) -> Result<serde_json::Value, serde_json::Error> {
+		serde_json::from_slice(&self.body)
+	}
+
+	pub fn format_body(&self) -> Result<String, PostWomanError> {
+		match self.headers.get("Content-Type") {
+			None => Ok(self.text()),
+			Some(v) => {
+				let content_type = v.to_str()?;
+				if content_type.starts_with("application/json") {
+					Ok(serde_json::to_string_pretty(&self.json()?)?)
+				} else if content_type.starts_with("text/plain") || content_type.starts_with("text/html") {
+					Ok(self.text())
+				} else {
+					Ok(format!("base64({})\n", BASE64_STANDARD.encode(&self.body)))
+				}
+			},
+		}
+	}
+}
+
+impl StringOr<ExtractorConfig> {
+	pub fn extract(&self, res: &BufferedResponse) -> Result<String, PostWomanError> {
+		match self {
+			StringOr::T(ExtractorConfig::Discard) => Ok("".to_string()),
+			StringOr::T(ExtractorConfig::Body) => res.format_body(),
+			StringOr::T(ExtractorConfig::Debug) => Ok(format!("{res:#?}\nBody: {}", res.format_body()?)),
+			StringOr::T(ExtractorConfig::Header { key }) => Ok(
+				res
+					.headers
+					.get(key)
+					.ok_or_else(|| PostWomanError::HeaderNotFound(key.clone()))?
+					.to_str()?
+					.to_string()
+			),
+			StringOr::T(ExtractorConfig::Regex { pattern }) => {
+				let pattern = regex::Regex::new(pattern)?;
+				let body = res.format_body()?;
+				Ok(
+					pattern.find(&body)
+						.ok_or_else(|| PostWomanError::NoMatch(body.clone()))?
+						.as_str()
+						.to_string()
+				)
+			},
+			// bare string defaults to JQL query
+			StringOr::T(ExtractorConfig::JQ { query }) | StringOr::Str(query) => {
+				let selection = jq(query, res.json()?)?;
+				if selection.len() == 1 {
+					Ok(stringify_json(&selection[0]))
+				} else {
+					Ok(serde_json::to_string_pretty(&selection)?)
+				}
+			},
+		}
+	}
+}
+
+pub(super) fn jq(query: &str, value: serde_json::Value) -> Result<Vec<serde_json::Value>, PostWomanError> {
+	// TODO am i not getting jaq api? or is it just this weird????
+	let mut defs = jaq_interpret::ParseCtx::new(Vec::new());
+	let (filter, errs) = jaq_parse::parse(query, jaq_parse::main());
+	let Some(filter) = filter else {
+		return Err(PostWomanError::JQError(
+			errs.into_iter().map(|x| format!("{x:?}")).collect::<Vec<String>>().join(", ")
+		));
+	};
+	let filter = defs.compile(filter);
+	if !defs.errs.is_empty() {
+		return Err(PostWomanError::JQError(
+			defs.errs.into_iter().map(|(e, _span)| e.to_string()).collect::<Vec<String>>().join(", ")
+		));
+	}
+	let out: Vec<serde_json::Value> = filter
+		.run((
+			jaq_interpret::Ctx::new([], &jaq_interpret::RcIter::new(core::iter::empty())),
+			jaq_interpret::Val::from(value)
+		))
+		.filter_map(|x| Some(x.ok()?.into()))
+		.collect();
+
+	Ok(out)
+}
@@ -1,10 +1,28 @@
+mod assertion;
+mod auth;
+mod body;
 mod client;
+mod cookies;
 mod endpoint;
 mod extractor;
+mod oauth2;
+mod retry;
+mod throttle;
+mod timings;
+mod tls;
 
-pub use client::ClientConfig;
-pub use endpoint::EndpointConfig;
-pub use extractor::ExtractorConfig;
+pub use assertion::AssertConfig;
+pub use auth::{redact, redact_error, ApiKeyLocation, AuthConfig, REDACTED};
+pub use body::{BodyConfig, FileBody, TypedBody};
+pub use client::{ClientConfig, HttpVersion};
+pub use cookies::CookieJar;
+pub use endpoint::{EndpointConfig, EndpointOutput, Exchange};
+pub use extractor::{BufferedResponse, ExtractorConfig};
+pub use oauth2::{OAuth2Config, TokenStore};
+pub use retry::RetryConfig;
+pub use throttle::{rate_interval, HostThrottle};
+pub use timings::Timings;
+pub use tls::TlsConfig;
 
 #[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PostWomanCollection {
@@ -17,4 +35,54 @@ pub struct PostWomanCollection {
 	#[serde(default)]
 	pub route: indexmap::IndexMap<String, EndpointConfig>,
 	// it's weird to name it singular but makes more sense in config
+	/// routes run in order before routes of this collection, whatever the filter. if one fails the collection is skipped
+	#[serde(default)]
+	pub setup: indexmap::IndexMap<String, EndpointConfig>,
+	/// routes run in order once the whole run is over, even if something failed, to clean up after setup
+	#[serde(default)]
+	pub teardown: indexmap::IndexMap<String, EndpointConfig>,
+	/// variables supplied by selected env profile, with the profile they come from
+	#[serde(skip)]
+	pub env_profile: indexmap::IndexMap<String, String>,
+}
+
+/// variables visible to a collection, by where they come from
+#[derive(Debug, Default, Clone)]
+pub struct EnvLayers {
+	/// plain `[env]` tables
+	pub base: toml::Table,
+	/// selected profiles from `[env.profiles.<name>]` tables
+	pub profile: toml::Table,
+}
+
+impl PostWomanCollection {
+	/// pick given profile out of env and layer variables inherited from parent collection on top. the final env has
+	/// parent profile over own profile over parent env over own env (process env still wins over all of them).
+	/// returns layers to pass to included collections, and whether this collection defines requested profile (None if it has no profiles)
+	pub fn apply_profile(&mut self, profile: Option<&str>, parent: &EnvLayers) -> Result<(EnvLayers, Option<bool>), crate::PostWomanError> {
+		let mut base = std::mem::take(&mut self.env);
+		let profiles = match base.remove("profiles") {
+			None => toml::Table::new(),
+			Some(toml::Value::Table(x)) => x,
+			Some(_) => return Err(crate::PostWomanError::InvalidProfile("`env.profiles` must be a table of profiles".to_string())),
+		};
+
+		let (mut own, found) = match profile.and_then(|name| profiles.get(name)) {
+			None => (toml::Table::new(), false),
+			Some(toml::Value::Table(x)) => (x.clone(), true),
+			Some(_) => return Err(crate::PostWomanError::InvalidProfile(format!("`env.profiles.{}` must be a table", profile.unwrap_or_default()))),
+		};
+
+		base.extend(parent.base.clone());
+		own.extend(parent.profile.clone());
+
+		self.env = base.clone();
+		self.env.extend(own.clone());
+		if let Some(name) = profile {
+			self.env_profile = own.keys().map(|k| (k.clone(), name.to_string())).collect();
+		}
+
+		let found = if profiles.is_empty() { None } else { Some(found) };
+		Ok((EnvLayers { base, profile: own }, found))
+	}
 }
@@ -0,0 +1,436 @@
+use crate::ext::FillableFromEnvironment;
+use crate::PostWomanError;
+
+use super::auth::REDACTED;
+
+// consider tokens expired a bit earlier, so they don't expire mid-flight
+const EXPIRY_MARGIN_SECS: i64 = 30;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OAuth2Config {
+	/// token endpoint, always absolute
+	pub token_url: String,
+	/// grant to request tokens with, defaults to client_credentials
+	pub grant: Option<OAuth2Grant>,
+	pub client_id: String,
+	pub client_secret: Option<String>,
+	/// resource owner username, for password grant
+	pub username: Option<String>,
+	/// resource owner password, for password grant
+	pub password: Option<String>,
+	/// space separated scopes to request
+	pub scope: Option<String>,
+	/// keep tokens on disk across runs until they expire, defaults to false
+	pub cache: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuth2Grant {
+	#[default]
+	ClientCredentials,
+	Password,
+}
+
+impl OAuth2Config {
+	/// values which should never show up in outputs
+	pub fn secrets(&self) -> Vec<String> {
+		[&self.client_secret, &self.password]
+			.into_iter()
+			.flatten()
+			.filter(|x| !x.is_empty())
+			.cloned()
+			.collect()
+	}
+
+	/// same config, with credentials replaced, safe to display
+	pub fn redacted(&self) -> Self {
+		OAuth2Config {
+			client_secret: self.client_secret.as_ref().map(|_| REDACTED.to_string()),
+			password: self.password.as_ref().map(|_| REDACTED.to_string()),
+			..self.clone()
+		}
+	}
+}
+
+impl FillableFromEnvironment for OAuth2Config {
+	fn fill_vars(mut self, vars: &crate::ext::Vars) -> Self {
+
+		for (k, v) in vars {
+			let k_var = format!("${{{k}}}");
+			self.token_url = self.token_url.replace(&k_var, v);
+			self.client_id = self.client_id.replace(&k_var, v);
+			for field in [&mut self.client_secret, &mut self.username, &mut self.password, &mut self.scope].into_iter().flatten() {
+				*field = field.replace(&k_var, v);
+			}
+		}
+
+		self
+	}
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Token {
+	access_token: String,
+	refresh_token: Option<String>,
+	/// unix timestamp, None if server didn't tell
+	expires_at: Option<i64>,
+}
+
+impl Token {
+	fn valid(&self) -> bool {
+		self.expires_at.is_none_or(|x| x - EXPIRY_MARGIN_SECS > chrono::Utc::now().timestamp())
+	}
+}
+
+/// oauth2 tokens for one collection, shared by all its routes
+pub struct TokenStore {
+	config: OAuth2Config,
+	/// identifies this collection and config in disk cache
+	key: String,
+	/// file tokens are cached in, if it could be located at all
+	cache: Option<std::path::PathBuf>,
+	token: tokio::sync::Mutex<Option<Token>>,
+}
+
+impl TokenStore {
+	pub fn new(config: OAuth2Config, collection: &str) -> Self {
+		// tokens belong to whoever they were granted to, never hand them to another user of the same client
+		let key = format!(
+			"{collection}|{}|{:?}|{}|{}|{}",
+			config.token_url,
+			config.grant.unwrap_or_default(),
+			config.client_id,
+			config.username.as_deref().unwrap_or_default(),
+			config.scope.as_deref().unwrap_or_default(),
+		);
+		TokenStore { config, key, cache: cache_path(), token: tokio::sync::Mutex::new(None) }
+	}
+
+	/// current access token, fetching a new one if missing or expired
+	/// if `rejected` is the current token, it gets refreshed, unless another route already did
+	pub async fn token(&self, client: &reqwest::Client, rejected: Option<&str>) -> Result<String, PostWomanError> {
+		let mut token = self.token.lock().await;
+
+		if token.is_none() && self.config.cache.unwrap_or(false) {
+			*token = self.cache.as_deref().and_then(|x| load_cache(x, &self.key));
+		}
+
+		if let Some(ref current) = *token {
+			let stale = rejected.is_some_and(|x| x == current.access_token);
+			if current.valid() && !stale {
+				return Ok(current.access_token.clone());
+			}
+		}
+
+		let refresh_token = token.as_ref().and_then(|x| x.refresh_token.clone());
+		let fresh = match refresh_token {
+			Some(refresh) => match self.request(client, &[("grant_type", "refresh_token"), ("refresh_token", &refresh)]).await {
+				Ok(x) => x,
+				Err(e) => {
+					eprintln!(" ! could not refresh oauth2 token, requesting a new one: {e}");
+					self.grant(client).await?
+				},
+			},
+			None => self.grant(client).await?,
+		};
+
+		if let Some(path) = self.cache.as_deref().filter(|_| self.config.cache.unwrap_or(false)) {
+			if let Err(e) = store_cache(path, &self.key, &fresh) {
+				eprintln!(" ! could not cache oauth2 token: {e}");
+			}
+		}
+
+		let access_token = fresh.access_token.clone();
+		*token = Some(fresh);
+		Ok(access_token)
+	}
+
+	async fn grant(&self, client: &reqwest::Client) -> Result<Token, PostWomanError> {
+		match self.config.grant.unwrap_or_default() {
+			OAuth2Grant::ClientCredentials => self.request(client, &[("grant_type", "client_credentials")]).await,
+			OAuth2Grant::Password => {
+				let (Some(username), Some(password)) = (&self.config.username, &self.config.password) else {
+					return Err(PostWomanError::AuthError("password grant needs both username and password".to_string()));
+				};
+				self.request(client, &[("grant_type", "password"), ("username", username), ("password", password)]).await
+			},
+		}
+	}
+
+	async fn request(&self, client: &reqwest::Client, params: &[(&str, &str)]) -> Result<Token, PostWomanError> {
+		let mut form: Vec<(&str, &str)> = params.to_vec();
+		form.push(("client_id", &self.config.client_id));
+		if let Some(ref secret) = self.config.client_secret {
+			form.push(("client_secret", secret));
+		}
+		if let Some(ref scope) = self.config.scope {
+			form.push(("scope", scope));
+		}
+
+		let body = form.iter()
+			.map(|(k, v)| format!("{}={}", crate::ext::urlencode(k), crate::ext::urlencode(v)))
+			.collect::<Vec<_>>()
+			.join("&");
+
+		let res = client
+			.post(&self.config.token_url)
+			.header(http::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+			.header(http::header::ACCEPT, "application/json")
+			.body(body)
+			.send()
+			.await?;
+
+		// refusals often come from proxies or gateways, as html or nothing at all
+		let status = res.status();
+		let raw = res.text().await?;
+		let payload = serde_json::from_str::<serde_json::Value>(&raw).ok();
+		if !status.is_success() {
+			let reason = payload.as_ref()
+				.and_then(|x| x.get("error_description").or(x.get("error")))
+				.map(crate::ext::stringify_json)
+				.unwrap_or_else(|| status.to_string());
+			return Err(PostWomanError::AuthError(format!("token endpoint refused: {reason}")));
+		}
+		let Some(payload) = payload else {
+			return Err(PostWomanError::AuthError(format!("token endpoint returned {status} without json")));
+		};
+
+		let Some(access_token) = payload.get("access_token").and_then(|x| x.as_str()) else {
+			return Err(PostWomanError::AuthError("token endpoint returned no access_token".to_string()));
+		};
+
+		eprintln!(" @ [{}] obtained oauth2 token from {}", chrono::Local::now().format(crate::fmt::TIMESTAMP_FMT), self.config.token_url);
+
+		Ok(Token {
+			access_token: access_token.to_string(),
+			refresh_token: payload.get("refresh_token").and_then(|x| x.as_str()).map(|x| x.to_string()),
+			expires_at: payload.get("expires_in").and_then(|x| x.as_i64()).map(|x| chrono::Utc::now().timestamp() + x),
+		})
+	}
+}
+
+fn cache_path() -> Option<std::path::PathBuf> {
+	let base = std::env::var_os("XDG_CACHE_HOME")
+		.map(std::path::PathBuf::from)
+		.or_else(|| std::env::var_os("HOME").map(|x| std::path::PathBuf::from(x).join(".cache")))?;
+	Some(base.join("postwoman").join("oauth2.json"))
+}
+
+fn load_cache(path: &std::path::Path, key: &str) -> Option<Token> {
+	let raw = std::fs::read_to_string(path).ok()?;
+	let mut tokens: std::collections::HashMap<String, Token> = serde_json::from_str(&raw).ok()?;
+	tokens.remove(key).filter(|x| x.valid() || x.refresh_token.is_some())
+}
+
+fn store_cache(path: &std::path::Path, key: &str, token: &Token) -> std::io::Result<()> {
+	let mut tokens: std::collections::HashMap<String, Token> = std::fs::read_to_string(path)
+		.ok()
+		.and_then(|x| serde_json::from_str(&x).ok())
+		.unwrap_or_default();
+	tokens.retain(|_, x| x.valid() || x.refresh_token.is_some());
+	tokens.insert(key.to_string(), token.clone());
+
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+
+	let mut options = std::fs::OpenOptions::new();
+	options.write(true).create(true).truncate(true);
+	// tokens are credentials, keep them private
+	#[cfg(unix)]
+	std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+	use std::io::Write;
+	options.open(path)?.write_all(serde_json::to_string_pretty(&tokens)?.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::{BufRead, BufReader, Read, Write};
+
+	/// token server answering each connection with next canned response, returns request bodies it got
+	fn token_server(responses: Vec<(u16, &'static str)>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("could not bind mock server");
+		let url = format!("http://{}/token", listener.local_addr().expect("bound address"));
+		let handle = std::thread::spawn(move || {
+			let mut bodies = Vec::new();
+			for (status, body) in responses {
+				let (stream, _) = listener.accept().expect("mock server accept");
+				let mut reader = BufReader::new(stream);
+				let mut length = 0;
+				loop {
+					let mut line = String::new();
+					reader.read_line(&mut line).expect("request line");
+					if line.trim().is_empty() { break };
+					if let Some((k, v)) = line.split_once(':') {
+						if k.eq_ignore_ascii_case("content-length") {
+							length = v.trim().parse().expect("numeric content-length");
+						}
+					}
+				}
+				let mut request = vec![0; length];
+				reader.read_exact(&mut request).expect("request body");
+				bodies.push(String::from_utf8(request).expect("utf8 body"));
+
+				let content_type = if body.starts_with('{') { "application/json" } else { "text/html" };
+				let mut stream = reader.into_inner();
+				write!(
+					stream,
+					"HTTP/1.1 {status} Whatever\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+					body.len()
+				).expect("response");
+			}
+			bodies
+		});
+		(url, handle)
+	}
+
+	fn config(token_url: String) -> OAuth2Config {
+		OAuth2Config {
+			token_url,
+			grant: None,
+			client_id: "app".into(),
+			client_secret: Some("s3cret".into()),
+			username: None,
+			password: None,
+			scope: Some("read write".into()),
+			cache: None,
+		}
+	}
+
+	fn block_on<T>(fut: impl std::future::Future<Output = T>) -> T {
+		tokio::runtime::Builder::new_current_thread()
+			.enable_all()
+			.build()
+			.expect("test runtime")
+			.block_on(fut)
+	}
+
+	fn http() -> reqwest::Client {
+		reqwest::Client::builder().no_proxy().build().expect("http client")
+	}
+
+	#[test]
+	fn fetches_token_with_client_credentials() {
+		let (url, server) = token_server(vec![(200, r#"{"access_token":"abc","expires_in":3600}"#)]);
+		let store = TokenStore::new(config(url), "test");
+		assert_eq!(block_on(store.token(&http(), None)).expect("token"), "abc");
+		let bodies = server.join().expect("mock server");
+		assert_eq!(bodies, ["grant_type=client_credentials&client_id=app&client_secret=s3cret&scope=read%20write"]);
+	}
+
+	#[test]
+	fn reuses_valid_token() {
+		// server goes away after one request, asking again would fail
+		let (url, server) = token_server(vec![(200, r#"{"access_token":"abc","expires_in":3600}"#)]);
+		let store = TokenStore::new(config(url), "test");
+		let http = http();
+		block_on(async {
+			assert_eq!(store.token(&http, None).await.expect("token"), "abc");
+			assert_eq!(store.token(&http, None).await.expect("token"), "abc");
+			// some other token being rejected doesn't make ours stale
+			assert_eq!(store.token(&http, Some("old")).await.expect("token"), "abc");
+		});
+		assert_eq!(server.join().expect("mock server").len(), 1);
+	}
+
+	#[test]
+	fn renews_expired_token_with_refresh_token() {
+		let (url, server) = token_server(vec![
+			// expires within margin, so already stale
+			(200, r#"{"access_token":"abc","refresh_token":"r1","expires_in":10}"#),
+			(200, r#"{"access_token":"def","expires_in":3600}"#),
+		]);
+		let store = TokenStore::new(config(url), "test");
+		let http = http();
+		block_on(async {
+			assert_eq!(store.token(&http, None).await.expect("token"), "abc");
+			assert_eq!(store.token(&http, None).await.expect("token"), "def");
+		});
+		let bodies = server.join().expect("mock server");
+		assert!(bodies[1].starts_with("grant_type=refresh_token&refresh_token=r1&"), "{}", bodies[1]);
+	}
+
+	#[test]
+	fn refreshes_rejected_token() {
+		let (url, server) = token_server(vec![
+			(200, r#"{"access_token":"abc","refresh_token":"r1","expires_in":3600}"#),
+			(200, r#"{"access_token":"def","expires_in":3600}"#),
+		]);
+		let store = TokenStore::new(config(url), "test");
+		let http = http();
+		block_on(async {
+			let token = store.token(&http, None).await.expect("token");
+			// what routes do after a 401
+			assert_eq!(store.token(&http, Some(&token)).await.expect("token"), "def");
+		});
+		let bodies = server.join().expect("mock server");
+		assert!(bodies[1].starts_with("grant_type=refresh_token&refresh_token=r1&"), "{}", bodies[1]);
+	}
+
+	#[test]
+	fn requests_new_token_when_refresh_fails() {
+		let (url, server) = token_server(vec![
+			(200, r#"{"access_token":"abc","refresh_token":"r1","expires_in":3600}"#),
+			(400, r#"{"error":"invalid_grant"}"#),
+			(200, r#"{"access_token":"ghi","expires_in":3600}"#),
+		]);
+		let store = TokenStore::new(config(url), "test");
+		let http = http();
+		block_on(async {
+			store.token(&http, None).await.expect("token");
+			assert_eq!(store.token(&http, Some("abc")).await.expect("token"), "ghi");
+		});
+		let bodies = server.join().expect("mock server");
+		assert!(bodies[2].starts_with("grant_type=client_credentials&"), "{}", bodies[2]);
+	}
+
+	#[test]
+	fn reports_refusals_with_or_without_json() {
+		let (url, server) = token_server(vec![
+			(401, r#"{"error":"invalid_client","error_description":"bad secret"}"#),
+			(502, "<html>bad gateway</html>"),
+			(200, "<html>login page</html>"),
+		]);
+		let store = TokenStore::new(config(url), "test");
+		let http = http();
+		block_on(async {
+			let err = store.token(&http, None).await.expect_err("refused").to_string();
+			assert!(err.contains("bad secret"), "{err}");
+			let err = store.token(&http, None).await.expect_err("refused").to_string();
+			assert!(err.contains("502"), "{err}");
+			let err = store.token(&http, None).await.expect_err("not json").to_string();
+			assert!(err.contains("without json"), "{err}");
+		});
+		server.join().expect("mock server");
+	}
+
+	#[test]
+	fn caches_token_on_disk() {
+		let dir = std::env::temp_dir().join(format!("postwoman-oauth2-test-{}", std::process::id()));
+		let cached = |config: OAuth2Config| TokenStore { cache: Some(dir.join("oauth2.json")), ..TokenStore::new(config, "test") };
+
+		let (url, server) = token_server(vec![
+			(200, r#"{"access_token":"abc","expires_in":3600}"#),
+			(200, r#"{"access_token":"other","expires_in":3600}"#),
+		]);
+		let mut config = config(url);
+		config.cache = Some(true);
+		config.grant = Some(OAuth2Grant::Password);
+		config.username = Some("alice".into());
+		config.password = Some("pw".into());
+		let http = http();
+		block_on(async {
+			assert_eq!(cached(config.clone()).token(&http, None).await.expect("token"), "abc");
+			// a later run gets it from disk, without asking server again
+			assert_eq!(cached(config.clone()).token(&http, None).await.expect("token"), "abc");
+			// but someone else's token is never reused
+			config.username = Some("bob".into());
+			assert_eq!(cached(config).token(&http, None).await.expect("token"), "other");
+		});
+		assert_eq!(server.join().expect("mock server").len(), 2);
+		let _ = std::fs::remove_dir_all(dir);
+	}
+}
@@ -0,0 +1,213 @@
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::PostWomanError;
+
+use super::Exchange;
+
+/// statuses worth retrying when none are configured
+const DEFAULT_STATUSES: &[u16] = &[429, 502, 503, 504];
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RetryConfig {
+	/// how many times to try again after first attempt fails, defaults to 0
+	pub retries: Option<u32>,
+	/// milliseconds to wait before first retry, defaults to 1000
+	pub delay: Option<u64>,
+	/// how delay grows between retries, defaults to fixed
+	pub backoff: Option<Backoff>,
+	/// randomize exponential delays between 0 and computed value, defaults to true
+	pub jitter: Option<bool>,
+	/// never wait more than this many milliseconds, also caps `Retry-After`. defaults to 60000
+	pub max_delay: Option<u64>,
+	/// response statuses to retry, defaults to 429, 502, 503 and 504
+	pub statuses: Option<Vec<u16>>,
+	/// retry network errors (connection refused, timeouts...), defaults to true
+	pub network: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backoff {
+	/// always wait `delay`
+	#[default]
+	Fixed,
+	/// double delay after each attempt
+	Exponential,
+}
+
+impl RetryConfig {
+	/// these options, with the ones set in `other` taking precedence
+	pub fn merge(&self, other: &RetryConfig) -> RetryConfig {
+		RetryConfig {
+			retries: other.retries.or(self.retries),
+			delay: other.delay.or(self.delay),
+			backoff: other.backoff.or(self.backoff),
+			jitter: other.jitter.or(self.jitter),
+			max_delay: other.max_delay.or(self.max_delay),
+			statuses: other.statuses.clone().or(self.statuses.clone()),
+			network: other.network.or(self.network),
+		}
+	}
+
+	/// why and how long to wait before trying again after given attempt (starting from 1), None if it shouldn't be retried
+	pub fn next(&self, attempt: u32, outcome: &Result<Exchange, PostWomanError>) -> Option<(String, Duration)> {
+		if attempt > self.retries.unwrap_or(0) {
+			return None;
+		}
+
+		let max_delay = Duration::from_millis(self.max_delay.unwrap_or(60_000));
+		let reason = match outcome {
+			// invalid urls or headers will fail the same way every time
+			Err(PostWomanError::Request(e)) if e.is_builder() => return None,
+			Err(PostWomanError::Request(e)) if self.network.unwrap_or(true) => e.to_string(),
+			Err(_) => return None,
+			Ok(exchange) => {
+				let status = exchange.response.status;
+				if !self.statuses.as_deref().unwrap_or(DEFAULT_STATUSES).contains(&status.as_u16()) {
+					return None;
+				}
+				// server knows best when it will be ready again
+				if let Some(after) = retry_after(&exchange.response.headers) {
+					return Some((format!("status {status}, asked to retry after {}s", after.as_secs()), after.min(max_delay)));
+				}
+				format!("status {status}")
+			},
+		};
+
+		Some((reason, self.delay(attempt)))
+	}
+
+	/// how long to wait before retrying after given attempt, when server didn't say
+	fn delay(&self, attempt: u32) -> Duration {
+		let max_delay = Duration::from_millis(self.max_delay.unwrap_or(60_000));
+		let delay = Duration::from_millis(self.delay.unwrap_or(1000));
+		let delay = match self.backoff.unwrap_or_default() {
+			Backoff::Fixed => delay,
+			Backoff::Exponential => {
+				let grown = delay.saturating_mul(2u32.saturating_pow(attempt - 1)).min(max_delay);
+				if self.jitter.unwrap_or(true) {
+					// full jitter, so many clients failing together don't come back together
+					grown.mul_f64(random())
+				} else {
+					grown
+				}
+			},
+		};
+		delay.min(max_delay)
+	}
+}
+
+/// parse `Retry-After` header, either seconds or an http date
+fn retry_after(headers: &http::HeaderMap) -> Option<Duration> {
+	let value = headers.get(http::header::RETRY_AFTER)?.to_str().ok()?.trim();
+	if let Ok(secs) = value.parse::<u64>() {
+		return Some(Duration::from_secs(secs));
+	}
+	let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+	let secs = (date.timestamp() - chrono::Utc::now().timestamp()).max(0);
+	Some(Duration::from_secs(secs as u64))
+}
+
+// between 0 and 1, std hashers are randomly seeded so this is good enough for jitter
+fn random() -> f64 {
+	let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+	hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos());
+	(hasher.finish() as f64) / (u64::MAX as f64)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn headers(retry_after: &str) -> http::HeaderMap {
+		let mut headers = http::HeaderMap::new();
+		headers.insert(http::header::RETRY_AFTER, retry_after.parse().expect("valid header value"));
+		headers
+	}
+
+	#[test]
+	fn parses_retry_after_seconds() {
+		assert_eq!(retry_after(&headers("120")), Some(Duration::from_secs(120)));
+		assert_eq!(retry_after(&headers(" 0 ")), Some(Duration::ZERO));
+		assert_eq!(retry_after(&headers("soon")), None);
+		assert_eq!(retry_after(&headers("-5")), None);
+		assert_eq!(retry_after(&http::HeaderMap::new()), None);
+	}
+
+	#[test]
+	fn parses_retry_after_dates() {
+		let later = (chrono::Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+		let secs = retry_after(&headers(&later)).expect("valid date").as_secs();
+		assert!((88..=90).contains(&secs), "{secs}");
+		// dates in the past mean now
+		assert_eq!(retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")), Some(Duration::ZERO));
+	}
+
+	#[test]
+	fn fixed_backoff_keeps_delay() {
+		let config = RetryConfig { delay: Some(250), ..Default::default() };
+		for attempt in 1..5 {
+			assert_eq!(config.delay(attempt), Duration::from_millis(250));
+		}
+		assert_eq!(RetryConfig::default().delay(1), Duration::from_secs(1));
+		let capped = RetryConfig { delay: Some(5000), max_delay: Some(2000), ..Default::default() };
+		assert_eq!(capped.delay(1), Duration::from_secs(2));
+	}
+
+	#[test]
+	fn exponential_backoff_doubles_up_to_max() {
+		let config = RetryConfig {
+			delay: Some(100),
+			backoff: Some(Backoff::Exponential),
+			jitter: Some(false),
+			max_delay: Some(1000),
+			..Default::default()
+		};
+		let delays: Vec<u128> = (1..=6).map(|x| config.delay(x).as_millis()).collect();
+		assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+		// doesn't overflow on silly attempt counts
+		assert_eq!(config.delay(200), Duration::from_secs(1));
+	}
+
+	#[test]
+	fn jitter_stays_below_computed_delay() {
+		let config = RetryConfig { delay: Some(100), backoff: Some(Backoff::Exponential), ..Default::default() };
+		for _ in 0..50 {
+			assert!(config.delay(3) <= Duration::from_millis(400));
+		}
+	}
+
+	// connection refused, from a port which was just free
+	fn connect_error() -> reqwest::Error {
+		let port = std::net::TcpListener::bind("127.0.0.1:0").expect("free port").local_addr().expect("bound address").port();
+		let client = reqwest::Client::builder().no_proxy().build().expect("http client");
+		tokio::runtime::Builder::new_current_thread()
+			.enable_all()
+			.build()
+			.expect("test runtime")
+			.block_on(client.get(format!("http://127.0.0.1:{port}/")).send())
+			.expect_err("nothing is listening")
+	}
+
+	#[test]
+	fn gives_up_after_retries() {
+		let error = connect_error();
+		assert!(error.is_connect(), "{error}");
+		let outcome = Err(PostWomanError::Request(error));
+		let config = RetryConfig { retries: Some(2), delay: Some(10), ..Default::default() };
+		assert_eq!(config.next(1, &outcome).map(|(_, d)| d), Some(Duration::from_millis(10)));
+		assert!(config.next(2, &outcome).is_some());
+		assert!(config.next(3, &outcome).is_none());
+		let no_network = RetryConfig { network: Some(false), ..config };
+		assert!(no_network.next(1, &outcome).is_none());
+	}
+
+	#[test]
+	fn never_retries_builder_errors() {
+		let error = reqwest::Client::new().get("http://[::1").build().expect_err("invalid url");
+		assert!(error.is_builder());
+		let config = RetryConfig { retries: Some(3), delay: Some(10), ..Default::default() };
+		assert!(config.next(1, &Err(PostWomanError::Request(error))).is_none());
+	}
+}
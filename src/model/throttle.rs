@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::PostWomanError;
+
+/// spaces out requests sent to the same host, shared by all routes in a run
+#[derive(Debug, Default)]
+pub struct HostThrottle {
+	/// earliest instant next request to each host may be sent
+	next: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostThrottle {
+	/// wait until a request to given url can be sent without going over `rate` requests per second to its host
+	pub async fn wait(&self, url: &str, rate: f64) {
+		// rates are validated when parsed, anything else can't be throttled anyway
+		let Ok(interval) = rate_interval(rate) else { return };
+		// invalid urls will fail anyway when sent, no point in holding them back
+		let Ok(url) = reqwest::Url::parse(url) else { return };
+		let host = format!("{}:{}", url.host_str().unwrap_or_default(), url.port_or_known_default().unwrap_or_default());
+
+		// book a slot right away, so concurrent requests line up instead of all waking together
+		let slot = {
+			let mut next = self.next.lock().expect("host throttle poisoned");
+			let now = Instant::now();
+			let slot = next.get(&host).map_or(now, |x| now.max(*x));
+			next.insert(host, slot + interval);
+			slot
+		};
+
+		tokio::time::sleep_until(slot.into()).await;
+	}
+}
+
+/// time between requests to stay within `rate` requests per second, if that's a usable rate at all
+pub fn rate_interval(rate: f64) -> Result<Duration, PostWomanError> {
+	match Duration::try_from_secs_f64(1.0 / rate) {
+		// also catches nan, infinite and negative rates, and ones too fast to tell apart from no limit
+		Ok(interval) if rate > 0.0 && !interval.is_zero() => Ok(interval),
+		_ => Err(PostWomanError::InvalidRate(rate)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rejects_unusable_rates() {
+		assert_eq!(rate_interval(4.0).expect("valid rate"), Duration::from_millis(250));
+		assert_eq!(rate_interval(0.5).expect("valid rate"), Duration::from_secs(2));
+		assert_eq!(rate_interval(1e9).expect("valid rate"), Duration::from_nanos(1));
+		for rate in [0.0, -1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e12, f64::MIN_POSITIVE] {
+			assert!(rate_interval(rate).is_err(), "{rate}");
+		}
+	}
+}
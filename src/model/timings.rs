@@ -0,0 +1,179 @@
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// how long each phase of a request took. connection phases are missing when an already open connection was reused
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct Timings {
+	/// resolving host name
+	#[serde(serialize_with = "millis_opt")]
+	pub dns: Option<Duration>,
+	/// establishing connection, including tls handshake for https: reqwest connectors don't tell them apart
+	#[serde(serialize_with = "millis_opt")]
+	pub connect: Option<Duration>,
+	/// from connection being ready to first response byte, mostly server time
+	#[serde(serialize_with = "millis")]
+	pub wait: Duration,
+	/// reading response body
+	#[serde(serialize_with = "millis")]
+	pub download: Duration,
+}
+
+impl std::fmt::Display for Timings {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let phases = [("dns", self.dns), ("connect", self.connect), ("wait", Some(self.wait)), ("download", Some(self.download))];
+		let out = phases.iter()
+			.filter_map(|(name, d)| d.map(|d| format!("{name} {:.1}ms", d.as_secs_f64() * 1000.0)))
+			.collect::<Vec<_>>()
+			.join(", ");
+		write!(f, "{out}")
+	}
+}
+
+/// instants marked while a connection is being opened for a request
+#[derive(Debug, Default)]
+pub(super) struct Marks {
+	connecting: Option<Instant>,
+	dns_start: Option<Instant>,
+	dns_end: Option<Instant>,
+	connected: Option<Instant>,
+}
+
+impl Marks {
+	/// split time between sending request and fully reading response into phases
+	pub(super) fn timings(&self, started: Instant, headers: Instant, done: Instant) -> Timings {
+		let dns = self.dns_start.zip(self.dns_end).map(|(a, b)| b - a);
+		// resolving happens inside connector, so it's not part of connection time
+		let connect = self.connected.map(|x| x.saturating_duration_since(self.dns_end.or(self.connecting).unwrap_or(started)));
+		Timings {
+			dns,
+			connect,
+			wait: headers.saturating_duration_since(self.connected.unwrap_or(started)),
+			download: done - headers,
+		}
+	}
+}
+
+tokio::task_local! {
+	/// marks for request currently being sent by this task
+	static MARKS: Arc<Mutex<Marks>>;
+}
+
+fn mark(f: impl FnOnce(&mut Marks)) {
+	// connections opened in background for other requests don't belong to anyone
+	let _ = MARKS.try_with(|marks| f(&mut marks.lock().expect("timing marks poisoned")));
+}
+
+/// run a request future, collecting connection marks it produces
+pub(super) async fn measure<F: std::future::Future>(marks: Arc<Mutex<Marks>>, fut: F) -> F::Output {
+	MARKS.scope(marks, fut).await
+}
+
+/// system resolver, timing lookups done while opening connections
+pub(super) struct TimingResolver;
+
+impl reqwest::dns::Resolve for TimingResolver {
+	fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+		let host = name.as_str().to_string();
+		Box::pin(async move {
+			mark(|m| m.dns_start = Some(Instant::now()));
+			let addrs = tokio::net::lookup_host((host, 0)).await?;
+			mark(|m| m.dns_end = Some(Instant::now()));
+			Ok(Box::new(addrs) as reqwest::dns::Addrs)
+		})
+	}
+}
+
+/// wraps reqwest connector, to know when a new connection started opening and when it was ready for requests
+#[derive(Clone)]
+pub(super) struct TimingLayer;
+
+impl<S> tower_layer::Layer<S> for TimingLayer {
+	type Service = TimingConnector<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		TimingConnector { inner }
+	}
+}
+
+#[derive(Clone)]
+pub(super) struct TimingConnector<S> {
+	inner: S,
+}
+
+impl<S, R> tower_service::Service<R> for TimingConnector<S>
+where
+	S: tower_service::Service<R>,
+	S::Future: Send + 'static,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: R) -> Self::Future {
+		mark(|m| *m = Marks { connecting: Some(Instant::now()), ..Default::default() });
+		let connecting = self.inner.call(req);
+		Box::pin(async move {
+			let conn = connecting.await;
+			mark(|m| m.connected = Some(Instant::now()));
+			conn
+		})
+	}
+}
+
+fn millis<S: serde::Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+	s.serialize_f64(d.as_secs_f64() * 1000.0)
+}
+
+fn millis_opt<S: serde::Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+	match d {
+		Some(d) => millis(d, s),
+		None => s.serialize_none(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::{Read, Write};
+
+	#[test]
+	fn marks_connection_phases() {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("could not bind mock server");
+		let port = listener.local_addr().expect("bound address").port();
+		let server = std::thread::spawn(move || {
+			let (mut stream, _) = listener.accept().expect("mock server accept");
+			let mut buf = [0; 1024];
+			let _ = stream.read(&mut buf).expect("request");
+			stream.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").expect("response");
+		});
+
+		let client = reqwest::Client::builder()
+			.no_proxy()
+			.dns_resolver(Arc::new(TimingResolver))
+			.connector_layer(TimingLayer)
+			.build()
+			.expect("http client");
+		let marks = Arc::new(Mutex::new(Marks::default()));
+		let started = Instant::now();
+		tokio::runtime::Builder::new_current_thread()
+			.enable_all()
+			.build()
+			.expect("test runtime")
+			.block_on(measure(marks.clone(), client.get(format!("http://localhost:{port}/")).send()))
+			.expect("response");
+		server.join().expect("mock server");
+
+		let marks = marks.lock().expect("timing marks poisoned");
+		assert!(marks.connecting.is_some() && marks.connected.is_some(), "{marks:?}");
+		assert!(marks.dns_start.is_some() && marks.dns_end.is_some(), "{marks:?}");
+		let now = Instant::now();
+		let timings = marks.timings(started, now, now);
+		assert!(timings.dns.is_some() && timings.connect.is_some());
+		assert!(timings.wait <= now - started);
+	}
+}
@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use sha2::{Digest, Sha256};
+
+use crate::ext::FillableFromEnvironment;
+use crate::PostWomanError;
+
+use super::auth::REDACTED;
+use super::HttpVersion;
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TlsConfig {
+	/// PEM file with root certificates to trust, besides system ones
+	pub ca: Option<String>,
+	/// PEM client certificate, for mutual TLS
+	pub cert: Option<String>,
+	/// PEM PKCS#8 private key for `cert`, can be omitted if `cert` file contains it too
+	pub key: Option<String>,
+	/// PKCS#12 client identity, alternative to `cert` and `key`
+	pub pkcs12: Option<String>,
+	/// password for `pkcs12` identity
+	pub password: Option<String>,
+	/// minimum TLS version to negotiate: "1.0", "1.1", "1.2" or "1.3"
+	pub min_version: Option<String>,
+	/// accept only servers presenting a certificate with one of these SHA-256 fingerprints, in hex
+	pub pin: Option<Vec<String>>,
+	/// accept invalid certificates, overriding client `accept_invalid_certs`
+	pub accept_invalid_certs: Option<bool>,
+}
+
+impl TlsConfig {
+	/// these options, with the ones set in `other` taking precedence
+	pub fn merge(&self, other: &TlsConfig) -> TlsConfig {
+		// client identity is replaced as a whole, mixing pem and pkcs12 makes no sense
+		let identity = if other.cert.is_some() || other.pkcs12.is_some() { other } else { self };
+		TlsConfig {
+			ca: other.ca.clone().or(self.ca.clone()),
+			cert: identity.cert.clone(),
+			key: identity.key.clone(),
+			pkcs12: identity.pkcs12.clone(),
+			password: identity.password.clone(),
+			min_version: other.min_version.clone().or(self.min_version.clone()),
+			pin: other.pin.clone().or(self.pin.clone()),
+			accept_invalid_certs: other.accept_invalid_certs.or(self.accept_invalid_certs),
+		}
+	}
+
+	/// apply these options on a client being built
+	pub fn configure(
+		&self,
+		mut builder: reqwest::ClientBuilder,
+		accept_invalid_certs: bool,
+		http_version: Option<HttpVersion>,
+	) -> Result<reqwest::ClientBuilder, PostWomanError> {
+		if self.cert.is_some() && self.pkcs12.is_some() {
+			return Err(PostWomanError::TlsError("set either `cert` or `pkcs12`, not both".to_string()));
+		}
+
+		let min_version = match self.min_version.as_deref().map(|x| x.trim_start_matches("TLS").trim_start_matches("v").trim()) {
+			None => None,
+			Some("1.0") => Some(reqwest::tls::Version::TLS_1_0),
+			Some("1.1") => Some(reqwest::tls::Version::TLS_1_1),
+			Some("1.2") => Some(reqwest::tls::Version::TLS_1_2),
+			Some("1.3") => Some(reqwest::tls::Version::TLS_1_3),
+			Some(other) => return Err(PostWomanError::TlsError(format!("unsupported minimum TLS version '{other}'"))),
+		};
+
+		// pins must be checked while handshaking, before anything is sent, and native-tls can't do that.
+		// native-tls can't require TLS 1.3 either, while rustls never goes below TLS 1.2 anyway
+		let tls13 = min_version == Some(reqwest::tls::Version::TLS_1_3);
+		if self.pin.is_some() || tls13 {
+			return Ok(builder.use_preconfigured_tls(self.rustls(tls13, accept_invalid_certs, http_version)?));
+		}
+
+		if let Some(ref ca) = self.ca {
+			for cert in reqwest::Certificate::from_pem_bundle(&read(ca)?)? {
+				builder = builder.add_root_certificate(cert);
+			}
+		}
+
+		if let Some(ref cert) = self.cert {
+			let cert_pem = read(cert)?;
+			let key_pem = match self.key {
+				Some(ref key) => read(key)?,
+				None => cert_pem.clone(),
+			};
+			builder = builder.identity(reqwest::Identity::from_pkcs8_pem(&cert_pem, &key_pem)?);
+		}
+
+		if let Some(ref pkcs12) = self.pkcs12 {
+			let identity = reqwest::Identity::from_pkcs12_der(&read(pkcs12)?, self.password.as_deref().unwrap_or_default())?;
+			builder = builder.identity(identity);
+		}
+
+		if let Some(version) = min_version {
+			builder = builder.min_tls_version(version);
+		}
+
+		Ok(builder)
+	}
+
+	// rustls config, refusing to handshake with servers not presenting a pinned certificate if there are pins
+	fn rustls(&self, tls13: bool, accept_invalid_certs: bool, http_version: Option<HttpVersion>) -> Result<rustls::ClientConfig, PostWomanError> {
+		let provider = Arc::new(rustls::crypto::ring::default_provider());
+		let pins = match self.pin {
+			Some(ref pins) => Some(pins.iter().map(|x| fingerprint(x)).collect::<Result<Vec<_>, _>>()?),
+			None => None,
+		};
+
+		let chain = if accept_invalid_certs {
+			None
+		} else {
+			let mut roots = rustls::RootCertStore::empty();
+			roots.add_parsable_certificates(system_roots());
+			if let Some(ref ca) = self.ca {
+				roots.add_parsable_certificates(certs(&read(ca)?)?);
+			}
+			let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+				.build()
+				.map_err(|e| PostWomanError::TlsError(format!("could not load trusted roots: {e}")))?;
+			Some(verifier)
+		};
+
+		let versions: &[&rustls::SupportedProtocolVersion] = if tls13 { &[&rustls::version::TLS13] } else { rustls::DEFAULT_VERSIONS };
+		let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+			.with_protocol_versions(versions)
+			.map_err(|e| PostWomanError::TlsError(e.to_string()))?
+			.dangerous()
+			.with_custom_certificate_verifier(Arc::new(PinVerifier { pins, chain, provider }));
+
+		let mut config = match (&self.cert, &self.pkcs12) {
+			(_, Some(_)) => return Err(PostWomanError::TlsError("`pkcs12` identities can't be used with pins or TLS 1.3, use `cert` and `key` instead".to_string())),
+			(Some(cert), None) => {
+				let cert_pem = read(cert)?;
+				let key_pem = match self.key {
+					Some(ref key) => read(key)?,
+					None => cert_pem.clone(),
+				};
+				let Some(key) = rustls_pemfile::private_key(&mut key_pem.as_slice()).ok().flatten() else {
+					return Err(PostWomanError::TlsError(format!("no private key found for {cert:?}")));
+				};
+				builder.with_client_auth_cert(certs(&cert_pem)?, key).map_err(|e| PostWomanError::TlsError(e.to_string()))?
+			},
+			(None, None) => builder.with_no_client_auth(),
+		};
+
+		// reqwest leaves preconfigured backends alone, so ALPN is up to us
+		config.alpn_protocols = match http_version {
+			Some(HttpVersion::Http1) => vec![b"http/1.1".to_vec()],
+			Some(HttpVersion::H2c) => vec![b"h2".to_vec()],
+			None | Some(HttpVersion::Http2) => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+		};
+
+		Ok(config)
+	}
+
+	/// make referenced file paths relative to given directory, unless already absolute
+	pub fn relative_to(&mut self, dir: &std::path::Path) {
+		for file in [&mut self.ca, &mut self.cert, &mut self.key, &mut self.pkcs12].into_iter().flatten() {
+			*file = super::body::relative(dir, file);
+		}
+	}
+
+	/// values which should never show up in outputs
+	pub fn secrets(&self) -> Vec<String> {
+		self.password.iter().filter(|x| !x.is_empty()).cloned().collect()
+	}
+
+	/// same config, with credentials replaced, safe to display
+	pub fn redacted(&self) -> Self {
+		TlsConfig {
+			password: self.password.as_ref().map(|_| REDACTED.to_string()),
+			..self.clone()
+		}
+	}
+}
+
+impl FillableFromEnvironment for TlsConfig {
+	fn fill_vars(mut self, vars: &crate::ext::Vars) -> Self {
+
+		for (k, v) in vars {
+			let k_var = format!("${{{k}}}");
+			for field in [&mut self.ca, &mut self.cert, &mut self.key, &mut self.pkcs12, &mut self.password, &mut self.min_version].into_iter().flatten() {
+				*field = field.replace(&k_var, v);
+			}
+			for pin in self.pin.iter_mut().flatten() {
+				*pin = pin.replace(&k_var, v);
+			}
+		}
+
+		self
+	}
+}
+
+fn read(path: &str) -> Result<Vec<u8>, PostWomanError> {
+	std::fs::read(path).map_err(|e| PostWomanError::TlsError(format!("could not read {path:?}: {e}")))
+}
+
+/// checks server certificate against pins while handshaking, so nothing reaches servers which don't match
+#[derive(Debug)]
+struct PinVerifier {
+	/// None when nothing is pinned, only the chain is validated then
+	pins: Option<Vec<[u8; 32]>>,
+	/// usual certificate chain validation, None when accepting invalid certs
+	chain: Option<Arc<WebPkiServerVerifier>>,
+	provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinVerifier {
+	fn verify_server_cert(
+		&self,
+		end_entity: &CertificateDer<'_>,
+		intermediates: &[CertificateDer<'_>],
+		server_name: &ServerName<'_>,
+		ocsp_response: &[u8],
+		now: UnixTime,
+	) -> Result<ServerCertVerified, rustls::Error> {
+		if let Some(ref chain) = self.chain {
+			chain.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+		}
+		let Some(ref pins) = self.pins else {
+			return Ok(ServerCertVerified::assertion());
+		};
+		let fingerprint: [u8; 32] = Sha256::digest(end_entity.as_ref()).into();
+		if pins.contains(&fingerprint) {
+			Ok(ServerCertVerified::assertion())
+		} else {
+			let fingerprint: String = fingerprint.iter().map(|b| format!("{b:02x}")).collect();
+			Err(rustls::Error::General(format!("server certificate {fingerprint} doesn't match any pin")))
+		}
+	}
+
+	fn verify_tls12_signature(
+		&self,
+		message: &[u8],
+		cert: &CertificateDer<'_>,
+		dss: &DigitallySignedStruct,
+	) -> Result<HandshakeSignatureValid, rustls::Error> {
+		rustls::crypto::verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+	}
+
+	fn verify_tls13_signature(
+		&self,
+		message: &[u8],
+		cert: &CertificateDer<'_>,
+		dss: &DigitallySignedStruct,
+	) -> Result<HandshakeSignatureValid, rustls::Error> {
+		rustls::crypto::verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+	}
+
+	fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+		self.provider.signature_verification_algorithms.supported_schemes()
+	}
+}
+
+// pins are hex, colons between bytes are optional
+fn fingerprint(pin: &str) -> Result<[u8; 32], PostWomanError> {
+	let hex = pin.trim().replace(':', "");
+	(0..hex.len())
+		.step_by(2)
+		.map(|i| hex.get(i..i + 2).and_then(|x| u8::from_str_radix(x, 16).ok()))
+		.collect::<Option<Vec<u8>>>()
+		.and_then(|x| x.try_into().ok())
+		.ok_or_else(|| PostWomanError::TlsError(format!("invalid pin '{pin}', expected a SHA-256 fingerprint in hex")))
+}
+
+fn certs(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, PostWomanError> {
+	rustls_pemfile::certs(&mut &pem[..])
+		.collect::<Result<_, _>>()
+		.map_err(|e| PostWomanError::TlsError(format!("invalid PEM certificate: {e}")))
+}
+
+// rustls doesn't know about the system trust store, look for it where openssl would
+fn system_roots() -> Vec<CertificateDer<'static>> {
+	let probe = openssl_probe::probe();
+	let files: Vec<std::path::PathBuf> = match (probe.cert_file, probe.cert_dir) {
+		(Some(file), _) => vec![file],
+		(None, Some(dir)) => std::fs::read_dir(dir).into_iter().flatten().flatten().map(|x| x.path()).collect(),
+		(None, None) => vec![],
+	};
+	files.iter()
+		.filter_map(|x| std::fs::read(x).ok())
+		.flat_map(|x| rustls_pemfile::certs(&mut x.as_slice()).flatten().collect::<Vec<_>>())
+		.collect()
+}
@@ -0,0 +1,179 @@
+use indexmap::{IndexMap, IndexSet};
+
+use crate::{model, PostWomanCollection, PostWomanError};
+
+/// full names of routes given route depends on, resolved from its collection
+fn dependencies(
+	collections: &IndexMap<String, PostWomanCollection>,
+	namespace: &str,
+	endpoint: &model::EndpointConfig,
+) -> Vec<String> {
+	let siblings = collections.get(namespace).map(|x| &x.route);
+	endpoint.depends_on.iter()
+		.flatten()
+		.map(|dep| {
+			// routes in same collection can be referenced by name, anything else needs its full name
+			if siblings.is_some_and(|x| x.contains_key(dep)) {
+				crate::ext::full_name(namespace, dep)
+			} else {
+				dep.clone()
+			}
+		})
+		.collect()
+}
+
+/// routes to run as (namespace, name, dependencies), each one after everything it depends on. these are all routes
+/// matching pattern plus their dependencies, otherwise in collection order
+pub fn order(
+	collections: &IndexMap<String, PostWomanCollection>,
+	pattern: &regex::Regex,
+) -> Result<Vec<(String, String, Vec<String>)>, PostWomanError> {
+	let mut graph = IndexMap::new();
+	for (namespace, collection) in collections {
+		for (name, endpoint) in &collection.route {
+			let deps = dependencies(collections, namespace, endpoint);
+			graph.insert(crate::ext::full_name(namespace, name), (namespace.clone(), name.clone(), deps));
+		}
+	}
+
+	for (full_name, (_, _, deps)) in &graph {
+		if let Some(dep) = deps.iter().find(|x| !graph.contains_key(*x)) {
+			return Err(PostWomanError::InvalidDependency(format!("{full_name} depends on unknown route '{dep}'")));
+		}
+	}
+
+	// cycles are broken collections even when they don't involve routes being run
+	let mut all = IndexSet::new();
+	for full_name in graph.keys() {
+		visit(&graph, full_name, &mut all, &mut Vec::new())?;
+	}
+
+	let mut sorted = IndexSet::new();
+	for full_name in graph.keys().filter(|x| pattern.find(x).is_some()) {
+		visit(&graph, full_name, &mut sorted, &mut Vec::new())?;
+	}
+
+	Ok(
+		sorted.into_iter()
+			.map(|x| {
+				let (namespace, name, deps) = &graph[x];
+				(namespace.clone(), name.clone(), deps.clone())
+			})
+			.collect()
+	)
+}
+
+// depth first, so dependencies land in sorted set before their dependents
+fn visit<'a>(
+	graph: &'a IndexMap<String, (String, String, Vec<String>)>,
+	full_name: &'a str,
+	sorted: &mut IndexSet<&'a str>,
+	path: &mut Vec<&'a str>,
+) -> Result<(), PostWomanError> {
+	if sorted.contains(full_name) {
+		return Ok(());
+	}
+	if let Some(start) = path.iter().position(|x| *x == full_name) {
+		let cycle = path[start..].join(" -> ");
+		return Err(PostWomanError::InvalidDependency(format!("cycle {cycle} -> {full_name}")));
+	}
+
+	path.push(full_name);
+	for dep in &graph[full_name].2 {
+		visit(graph, dep, sorted, path)?;
+	}
+	path.pop();
+	sorted.insert(full_name);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(raw: &[(&str, &str)]) -> IndexMap<String, PostWomanCollection> {
+		raw.iter()
+			.map(|(namespace, collection)| (namespace.to_string(), toml::from_str(collection).expect("valid collection")))
+			.collect()
+	}
+
+	fn names(collections: &IndexMap<String, PostWomanCollection>, pattern: &str) -> Result<Vec<String>, PostWomanError> {
+		let pattern = regex::Regex::new(pattern).expect("valid regex");
+		Ok(order(collections, &pattern)?.into_iter().map(|(ns, name, _)| crate::ext::full_name(&ns, &name)).collect())
+	}
+
+	const API: &str = r#"
+		[route.list]
+		path = "/items"
+		depends_on = ["login", "create"]
+
+		[route.create]
+		path = "/items"
+		depends_on = ["login"]
+
+		[route.login]
+		path = "/login"
+
+		[route.health]
+		path = "/health"
+	"#;
+
+	#[test]
+	fn puts_dependencies_first() {
+		let collections = parse(&[("api", API)]);
+		assert_eq!(names(&collections, ".*").expect("valid plan"), ["api:login", "api:create", "api:list", "api:health"]);
+	}
+
+	#[test]
+	fn pulls_in_dependencies_of_matched_routes_only() {
+		let collections = parse(&[("api", API)]);
+		assert_eq!(names(&collections, "create").expect("valid plan"), ["api:login", "api:create"]);
+		assert_eq!(names(&collections, "health").expect("valid plan"), ["api:health"]);
+		assert!(names(&collections, "nothing").expect("valid plan").is_empty());
+	}
+
+	#[test]
+	fn resolves_dependencies_across_collections() {
+		let collections = parse(&[
+			("auth", "[route.login]\npath = \"/login\""),
+			// siblings win over full names, other collections need the full one
+			("api", "[route.me]\npath = \"/me\"\ndepends_on = [\"auth:login\", \"login\"]\n[route.login]\npath = \"/api/login\""),
+		]);
+		let plan = order(&collections, &regex::Regex::new("me").expect("valid regex")).expect("valid plan");
+		let names: Vec<_> = plan.iter().map(|(ns, name, _)| crate::ext::full_name(ns, name)).collect();
+		assert_eq!(names, ["auth:login", "api:login", "api:me"]);
+		assert_eq!(plan[2].2, ["auth:login", "api:login"]);
+	}
+
+	#[test]
+	fn rejects_unknown_dependencies() {
+		let collections = parse(&[("api", "[route.me]\npath = \"/me\"\ndepends_on = [\"auth:login\"]")]);
+		let err = names(&collections, ".*").expect_err("unknown dependency").to_string();
+		assert!(err.contains("api:me depends on unknown route 'auth:login'"), "{err}");
+	}
+
+	#[test]
+	fn rejects_cycles_even_outside_filter() {
+		let collections = parse(&[("api", r#"
+			[route.a]
+			path = "/a"
+			depends_on = ["b"]
+
+			[route.b]
+			path = "/b"
+			depends_on = ["c"]
+
+			[route.c]
+			path = "/c"
+			depends_on = ["a"]
+
+			[route.standalone]
+			path = "/standalone"
+		"#)]);
+		let err = names(&collections, "standalone").expect_err("cycle").to_string();
+		assert!(err.contains("cycle api:a -> api:b -> api:c -> api:a"), "{err}");
+
+		let selfish = parse(&[("api", "[route.a]\npath = \"/a\"\ndepends_on = [\"a\"]")]);
+		assert!(names(&selfish, ".*").is_err());
+	}
+}